@@ -0,0 +1,184 @@
+//! Benchmark measurements: per-generation timings of an engine and their summary statistics
+use std::time::Instant;
+use crate::memory::MemoryUse;
+use crate::traits::LifeUniverse;
+
+/// Two-sided 95% critical values of Student's t distribution for 1 to 30 degrees of freedom
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+/// Two-sided 95% critical value of Student's t distribution for `dof` degrees of freedom,
+/// rounded down to a whole number of degrees, which errs on the side of caution
+fn t_95_dof(dof: f64) -> f64 {
+    match dof.floor() as usize {
+        0 => f64::INFINITY,
+        n if n <= T_95.len() => T_95[n - 1],
+        _ => 1.960,
+    }
+}
+
+/// Critical value of the 95% confidence interval of a mean of `samples` samples
+fn t_95(samples: usize) -> f64 {
+    match samples {
+        0 | 1 => 0.0,
+        n => t_95_dof((n - 1) as f64),
+    }
+}
+
+/// Ticks `universe` `generations` times, returning how long each generation took in nanoseconds
+pub fn time_generations(universe: &mut dyn LifeUniverse, generations: usize) -> Vec<u64> {
+    (0..generations)
+        .map(|_| {
+            let start = Instant::now();
+            universe.tick();
+            start.elapsed().as_nanos() as u64
+        })
+        .collect()
+}
+
+/// `p`th percentile (0 to 100) of sorted samples, interpolating linearly between ranks
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+/// Summary statistics of a set of samples, in the samples' unit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub samples: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,  // Sample standard deviation, 0 for a single sample
+    pub min: f64,
+    pub max: f64,
+    pub p95: f64,
+    pub ci_low: f64,  // 95% confidence interval of the mean; empty for a single sample
+    pub ci_high: f64,
+}
+
+impl Summary {
+    /// Summarizes `samples`, of which there must be at least one
+    pub fn of(samples: &[u64]) -> Summary {
+        assert!(!samples.is_empty(), "Cannot summarize an empty set of samples");
+        let mut sorted: Vec<f64> = samples.iter().map(|&sample| sample as f64).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let stddev = if n > 1 {
+            (sorted.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        let margin = t_95(n) * stddev / (n as f64).sqrt();
+
+        Summary {
+            samples: n,
+            mean,
+            median: percentile(&sorted, 50.0),
+            stddev,
+            min: sorted[0],
+            max: sorted[n - 1],
+            p95: percentile(&sorted, 95.0),
+            ci_low: mean - margin,
+            ci_high: mean + margin,
+        }
+    }
+}
+
+/// Measurements of one engine on one grid size
+#[derive(Clone, Debug, PartialEq)]
+pub struct EngineResult {
+    pub engine: String,
+    pub width: usize,
+    pub height: usize,
+    pub generation_ns: Vec<Vec<u64>>, // Time of every generation of every measured repetition
+    pub memory: Vec<MemoryUse>,      // Memory use of every measured repetition
+}
+
+/// Label of a grid size in the results and on the console, "512" or "640x480"
+pub fn size_label(width: usize, height: usize) -> String {
+    if width == height {
+        width.to_string()
+    } else {
+        format!("{}x{}", width, height)
+    }
+}
+
+impl EngineResult {
+    /// Grid size as reported, see `size_label`
+    pub fn size(&self) -> String {
+        size_label(self.width, self.height)
+    }
+
+    /// Total time of each repetition in nanoseconds
+    pub fn totals(&self) -> Vec<u64> {
+        self.generation_ns.iter().map(|times| times.iter().sum()).collect()
+    }
+
+    /// Statistics of the total times of the repetitions
+    pub fn summary(&self) -> Summary {
+        Summary::of(&self.totals())
+    }
+
+    /// Highest memory use of any repetition
+    pub fn peak_memory(&self) -> MemoryUse {
+        self.memory.iter().fold(MemoryUse::default(), |peak, &memory| peak.max(memory))
+    }
+
+    /// Median time of each generation across the repetitions
+    pub fn generation_medians(&self) -> Vec<u64> {
+        let generations = self.generation_ns.iter().map(Vec::len).min().unwrap_or(0);
+        (0..generations)
+            .map(|generation| {
+                let times: Vec<u64> = self.generation_ns.iter().map(|times| times[generation]).collect();
+                Summary::of(&times).median.round() as u64
+            })
+            .collect()
+    }
+}
+
+/// How a candidate's samples compare with a baseline's, by Welch's t-test on their means
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    pub baseline: Summary,
+    pub candidate: Summary,
+    pub speedup: f64,      // Baseline mean over candidate mean: above 1 when the candidate is faster
+    pub change: f64,       // Relative change of the mean, e.g. 0.1 when the candidate takes 10% longer
+    pub t: f64,            // Welch's t statistic of the candidate mean minus the baseline mean
+    pub dof: f64,          // Welch-Satterthwaite degrees of freedom
+    pub significant: bool, // Whether the means differ at the 95% level; never with fewer than 2 samples each
+}
+
+impl Comparison {
+    /// Compares two sets of samples, each of at least one sample
+    pub fn of(baseline: &[u64], candidate: &[u64]) -> Comparison {
+        let (b, c) = (Summary::of(baseline), Summary::of(candidate));
+        let (b_var, c_var) = (b.stddev * b.stddev / b.samples as f64, c.stddev * c.stddev / c.samples as f64);
+        let (t, dof) = if b.samples < 2 || c.samples < 2 {
+            (0.0, 0.0)
+        } else if b_var + c_var == 0.0 {
+            // Identical samples on either side differ exactly when their means do
+            let t = if c.mean == b.mean { 0.0 } else { (c.mean - b.mean).signum() * f64::INFINITY };
+            (t, (b.samples + c.samples - 2) as f64)
+        } else {
+            let dof = (b_var + c_var).powi(2)
+                / (b_var * b_var / (b.samples - 1) as f64 + c_var * c_var / (c.samples - 1) as f64);
+            ((c.mean - b.mean) / (b_var + c_var).sqrt(), dof)
+        };
+
+        Comparison {
+            baseline: b,
+            candidate: c,
+            speedup: b.mean / c.mean,
+            change: c.mean / b.mean - 1.0,
+            t,
+            dof,
+            significant: dof >= 1.0 && t.abs() > t_95_dof(dof),
+        }
+    }
+}
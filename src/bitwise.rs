@@ -1,21 +1,7 @@
-extern crate cfg_if;
-extern crate wasm_bindgen;
-
-use cfg_if::cfg_if;
 use wasm_bindgen::prelude::*;
-
-cfg_if! {
-    if #[cfg(feature = "wee_alloc")] {
-        extern crate wee_alloc;
-        #[global_allocator]
-        static ALLOC: wee_alloc::WeeAlloc = wee_alloc::INIT;
-    }
-}
-
-#[wasm_bindgen(start)]
-pub fn main() {
-    console_error_panic_hook::set_once();
-}
+use crate::traits::LifeUniverse;
+use crate::rules::Rule;
+use crate::topology::Topology;
 
 #[wasm_bindgen]
 #[repr(u8)]
@@ -30,6 +16,8 @@ pub struct Universe {
     width: usize,
     height: usize,
     cells: Vec<u8>, // Using Vec<u8> for bitwise implementation
+    rule: Rule,
+    topology: Topology,
 }
 
 impl Universe {
@@ -56,8 +44,11 @@ impl Universe {
                     continue;
                 }
 
-                let neighbor_row = ((row as isize + delta_row + self.height as isize) % self.height as isize) as usize;
-                let neighbor_col = ((column as isize + delta_col + self.width as isize) % self.width as isize) as usize;
+                let Some((neighbor_row, neighbor_col)) =
+                    self.topology.neighbour(self.width, self.height, row, column, delta_row, delta_col)
+                else {
+                    continue;
+                };
 
                 let (byte_index, bit_mask) = self.get_index(neighbor_row, neighbor_col);
                 if self.cells[byte_index] & bit_mask != 0 {
@@ -68,12 +59,35 @@ impl Universe {
 
         count
     }
+
+    /// 3x3 neighbourhood of a cell as a lookup-table index, bit `3 * row + col`
+    /// set for each live cell (the cell itself is bit 4)
+    fn neighbourhood(&self, row: usize, column: usize) -> usize {
+        let mut index = 0;
+
+        for delta_row in -1..=1 {
+            for delta_col in -1..=1 {
+                let Some((neighbor_row, neighbor_col)) =
+                    self.topology.neighbour(self.width, self.height, row, column, delta_row, delta_col)
+                else {
+                    continue;
+                };
+
+                let (byte_index, bit_mask) = self.get_index(neighbor_row, neighbor_col);
+                if self.cells[byte_index] & bit_mask != 0 {
+                    index |= 1 << (3 * (delta_row + 1) + delta_col + 1);
+                }
+            }
+        }
+
+        index
+    }
 }
 
 #[wasm_bindgen]
 impl Universe {
     pub fn new(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
-        let num_bytes = (width * height + 7) / 8;
+        let num_bytes = (width * height).div_ceil(8);
         let mut cells = vec![0u8; num_bytes];
 
         // Initialize from the flat matrix
@@ -90,6 +104,8 @@ impl Universe {
             width,
             height,
             cells,
+            rule: Rule::default(),
+            topology: Topology::default(),
         }
     }
 
@@ -99,15 +115,13 @@ impl Universe {
         for row in 0..self.height {
             for col in 0..self.width {
                 let (byte_index, bit_mask) = self.get_index(row, col);
-                let live_neighbors = self.live_neighbor_count(row, col);
                 let is_alive = self.cells[byte_index] & bit_mask != 0;
 
-                let next_state = match (is_alive, live_neighbors) {
-                    (true, x) if x < 2 => false,
-                    (true, 2) | (true, 3) => true,
-                    (true, x) if x > 3 => false,
-                    (false, 3) => true,
-                    _ => is_alive,
+                let next_state = if self.rule.is_totalistic() {
+                    let live_neighbors = self.live_neighbor_count(row, col);
+                    self.rule.next_state(is_alive, live_neighbors)
+                } else {
+                    self.rule.next_state_neighbourhood(self.neighbourhood(row, col))
                 };
 
                 if next_state {
@@ -147,6 +161,75 @@ impl std::fmt::Display for Universe {
     }
 }
 
+impl LifeUniverse for Universe {
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
+        Universe::new(width, height, flat_matrix)
+    }
+
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn supports_rule(&self, rule: &Rule) -> bool {
+        // One bit per cell leaves no room for dying states
+        !rule.is_generations()
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(self.supports_rule(&rule), "The bitwise engine does not support the rule {}", rule);
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn set_topology(&mut self, topology: Topology) {
+        assert!(self.supports_topology(topology), "The bitwise engine does not support the {} topology", topology);
+        topology.check_size(self.width, self.height);
+        self.topology = topology;
+    }
 
+    fn tick(&mut self) {
+        Universe::tick(self)
+    }
 
+    fn get_cell(&self, row: usize, col: usize) -> bool {
+        let (byte_index, bit_mask) = self.get_index(row, col);
+        self.cells[byte_index] & bit_mask != 0
+    }
 
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool) {
+        let (byte_index, bit_mask) = self.get_index(row, col);
+        if alive {
+            self.cells[byte_index] |= bit_mask;
+        } else {
+            self.cells[byte_index] &= !bit_mask;
+        }
+    }
+
+    fn population(&self) -> usize {
+        // Padding bits past `width * height` are never set, so every set bit is a live cell
+        self.cells.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        Box::new(
+            (0..self.width * self.height)
+                .filter(move |&idx| self.cells[idx / 8] & (1 << (idx % 8)) != 0)
+                .map(move |idx| (idx / self.width, idx % self.width)),
+        )
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn render(&self) -> String {
+        Universe::render(self)
+    }
+}
@@ -0,0 +1,457 @@
+//! Command line options of the `performance_test` binary
+use std::convert::TryFrom;
+use std::path::Path;
+use std::str::FromStr;
+use wasm_game_of_life::bench::size_label;
+use wasm_game_of_life::topology::Topology;
+use wasm_game_of_life::utils::MAX_DENSE_CELLS;
+
+/// Engines the benchmark can run, in the order they are run and reported
+pub const ENGINES: [&str; 8] = [
+    "Naive",
+    "Sparse",
+    "Optimized",
+    "TrackAliveCells",
+    "Parallel",
+    "HashParallel",
+    "Bitwise",
+    "Hashlife",
+];
+
+/// Largest scale `--scales` accepts: the engines start from a flat matrix, which holds at
+/// most `MAX_DENSE_CELLS` cells
+pub const MAX_SCALE: u32 = MAX_DENSE_CELLS.trailing_zeros() / 2 - 6;
+
+pub const USAGE: &str = "\
+Times the Game of Life engines on one or more patterns.
+
+Usage: performance_test [OPTIONS] [PATTERN]...
+       performance_test report [OPTIONS] <RESULTS>...
+       performance_test compare [OPTIONS] <BASELINE> <CANDIDATE>
+
+Arguments:
+  [PATTERN]...             .rle, .cells or Life 1.06/1.05 files to run [default: grids/blom.rle]
+
+Options:
+  -s, --scales <LIST>      Comma-separated scales k up to 10, each a square grid of side 2^(6+k) [default: 3]
+      --sizes <LIST>       Comma-separated grid sizes instead of scales, e.g. 100,640x480
+  -i, --iterations <N>     Generations to run per engine and size [default: 1000]
+  -e, --engines <LIST>     Comma-separated engines to run [default: all]
+  -x, --exclude <LIST>     Comma-separated engines to skip
+  -w, --warmup <N>         Discarded runs per engine and size before the measured ones [default: 1]
+  -r, --repetitions <N>    Measured runs per engine and size [default: 5]
+  -t, --topology <NAME>    Grid topology, e.g. torus or plane [default: the pattern's, else plane]
+  -o, --output <PATH>      Results file; {pattern} is replaced by the pattern file name
+                           [default: results_csv/{pattern}_<iterations>_<last scale>_results.<format>]
+  -f, --format <FORMAT>    csv or jsonl (JSON lines) [default: from the output extension, else csv]
+      --verify             Check that the engines agree instead of timing them
+      --check-every <K>    With --verify, compare the engines every K generations [default: 1]
+      --isolate            Run each engine, size and pattern in a fresh child process
+      --timeout <SECONDS>  With --isolate, stop a job that runs longer and carry on [default: 600]
+  -h, --help               Print this help
+
+Engines: Naive, Sparse, Optimized, TrackAliveCells, Parallel, HashParallel, Bitwise, Hashlife
+
+Run `performance_test report --help` to draw charts of results files, and
+`performance_test compare --help` to compare two of them.
+";
+
+pub const REPORT_USAGE: &str = "\
+Draws charts of results files (CSV or JSON lines): mean total time against grid size, time per 10 generations
+on each grid size, memory against grid size, and speedup over the naive engine.
+
+Usage: performance_test report [OPTIONS] <RESULTS>...
+
+Arguments:
+  <RESULTS>...             Results files written by performance_test
+
+Options:
+  -o, --out-dir <DIR>      Directory to write the charts into [default: charts]
+  -f, --format <FORMAT>    png or svg [default: png]
+  -h, --help               Print this help
+";
+
+pub const COMPARE_USAGE: &str = "\
+Compares two results files (CSV or JSON lines) engine by engine and grid size by grid size: the speedup of
+the candidate's mean total time over the baseline's, and whether the difference is significant at the 95%
+level by Welch's t-test over the repetitions. Exits with 1 if any engine is significantly slower by more
+than the threshold.
+
+Usage: performance_test compare [OPTIONS] <BASELINE> <CANDIDATE>
+
+Arguments:
+  <BASELINE>               Results file of the reference run
+  <CANDIDATE>              Results file of the run to check, of the same pattern
+
+Options:
+  -t, --threshold <PCT>    Slowdown in percent beyond which a significant difference fails [default: 5]
+  -h, --help               Print this help
+";
+
+/// Format of the results file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    JsonLines,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" | "json" | "ndjson" => Ok(OutputFormat::JsonLines),
+            _ => Err(format!("unknown output format '{}', expected csv or jsonl", s)),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// Image format of the charts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChartFormat {
+    Png,
+    Svg,
+}
+
+impl FromStr for ChartFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ChartFormat, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ChartFormat::Png),
+            "svg" => Ok(ChartFormat::Svg),
+            _ => Err(format!("unknown chart format '{}', expected png or svg", s)),
+        }
+    }
+}
+
+impl ChartFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ChartFormat::Png => "png",
+            ChartFormat::Svg => "svg",
+        }
+    }
+}
+
+/// Grid sizes to run, as `(width, height)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sizes {
+    /// Square grids of side `2^(6 + scale)`
+    Scales(Vec<u32>),
+    /// Explicit sizes
+    Explicit(Vec<(usize, usize)>),
+}
+
+impl Sizes {
+    pub fn grids(&self) -> Vec<(usize, usize)> {
+        match self {
+            Sizes::Scales(scales) => scales.iter().map(|&scale| (1 << (6 + scale), 1 << (6 + scale))).collect(),
+            Sizes::Explicit(sizes) => sizes.clone(),
+        }
+    }
+
+    /// Short description of the largest run, used in default output file names
+    pub fn last_label(&self) -> String {
+        match self {
+            Sizes::Scales(scales) => scales.last().map_or(String::new(), |scale| scale.to_string()),
+            Sizes::Explicit(sizes) => sizes.last().map_or(String::new(), |&(width, height)| size_label(width, height)),
+        }
+    }
+}
+
+/// Everything a benchmark run needs to know
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub patterns: Vec<String>,
+    pub sizes: Sizes,
+    pub iterations: usize,
+    pub engines: Vec<&'static str>,
+    pub warmup: usize,
+    pub repetitions: usize,
+    pub topology: Option<Topology>,
+    pub output: Option<String>,
+    pub format: OutputFormat,
+    pub verify: bool,        // Compare the engines' live cells instead of timing them
+    pub check_every: usize,  // Generations between comparisons with `verify`
+    pub isolate: bool,       // Run every job in a child process
+    pub timeout: u64,        // Seconds a child process may run with `isolate`
+    pub job: bool,           // This is a child process: run one engine on one size and report on stdout
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            patterns: vec!["grids/blom.rle".to_string()],
+            sizes: Sizes::Scales(vec![3]),
+            iterations: 1000,
+            engines: ENGINES.to_vec(),
+            warmup: 1,
+            repetitions: 5,
+            topology: None,
+            output: None,
+            format: OutputFormat::Csv,
+            verify: false,
+            check_every: 1,
+            isolate: false,
+            timeout: 600,
+            job: false,
+        }
+    }
+}
+
+impl Options {
+    /// Results file of `pattern`, the file name of the pattern being run
+    pub fn output_path(&self, pattern: &str) -> String {
+        match &self.output {
+            Some(output) => output.replace("{pattern}", pattern),
+            None => format!(
+                "results_csv/{}_{}_{}_results.{}",
+                pattern,
+                self.iterations,
+                self.sizes.last_label(),
+                self.format.extension()
+            ),
+        }
+    }
+}
+
+/// Results files to draw charts of
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportOptions {
+    pub results: Vec<String>,
+    pub out_dir: String,
+    pub format: ChartFormat,
+}
+
+/// Results files to compare
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompareOptions {
+    pub baseline: String,
+    pub candidate: String,
+    pub threshold: f64, // Largest tolerated slowdown, in percent
+}
+
+/// What the command line asks for
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Run(Options),
+    Report(ReportOptions),
+    Compare(CompareOptions),
+    Help,
+    ReportHelp,
+    CompareHelp,
+}
+
+/// Parses a comma-separated list, reporting the offending item
+fn parse_list<T, F>(option: &str, value: &str, parse: F) -> Result<Vec<T>, String>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| parse(item).ok_or_else(|| format!("invalid value '{}' for {}", item, option)))
+        .collect()
+}
+
+/// Parses `N` or `WxH` into a grid size
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = match size.split_once(['x', 'X']) {
+        Some((width, height)) => (width.parse().ok()?, height.parse().ok()?),
+        None => {
+            let side = size.parse().ok()?;
+            (side, side)
+        }
+    };
+    if width == 0 || height == 0 {
+        None
+    } else {
+        Some((width, height))
+    }
+}
+
+/// Canonical name of an engine, matched case-insensitively
+fn parse_engine(name: &str) -> Option<&'static str> {
+    ENGINES.iter().copied().find(|engine| engine.eq_ignore_ascii_case(name))
+}
+
+/// Splits `--option=value` into the option and its value
+fn split_inline(arg: &str) -> (String, Option<String>) {
+    match arg.split_once('=') {
+        Some((option, value)) if option.starts_with("--") => (option.to_string(), Some(value.to_string())),
+        _ => (arg.to_string(), None),
+    }
+}
+
+/// Parses the arguments that follow `report`
+fn parse_report_args<I>(args: I) -> Result<Command, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut results = Vec::new();
+    let mut out_dir = "charts".to_string();
+    let mut format = ChartFormat::Png;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = split_inline(&arg);
+        let mut value = || inline_value.clone().or_else(|| args.next()).ok_or_else(|| format!("missing value for {}", option));
+        match option.as_str() {
+            "-h" | "--help" => return Ok(Command::ReportHelp),
+            "-o" | "--out-dir" => out_dir = value()?,
+            "-f" | "--format" => format = value()?.parse()?,
+            _ if option.starts_with('-') && option.len() > 1 => return Err(format!("unknown option '{}'", option)),
+            _ => results.push(arg),
+        }
+    }
+    if results.is_empty() {
+        return Err("report needs at least one results file".to_string());
+    }
+    Ok(Command::Report(ReportOptions { results, out_dir, format }))
+}
+
+/// Parses the arguments that follow `compare`
+fn parse_compare_args<I>(args: I) -> Result<Command, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut files = Vec::new();
+    let mut threshold = 5.0;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = split_inline(&arg);
+        let mut value = || inline_value.clone().or_else(|| args.next()).ok_or_else(|| format!("missing value for {}", option));
+        match option.as_str() {
+            "-h" | "--help" => return Ok(Command::CompareHelp),
+            "-t" | "--threshold" => {
+                let pct = value()?;
+                threshold = match pct.trim_end_matches('%').parse::<f64>() {
+                    Ok(threshold) if threshold.is_finite() && threshold >= 0.0 => threshold,
+                    _ => return Err(format!("invalid value '{}' for {}, expected a percentage", pct, option)),
+                };
+            }
+            _ if option.starts_with('-') && option.len() > 1 => return Err(format!("unknown option '{}'", option)),
+            _ => files.push(arg),
+        }
+    }
+    match <[String; 2]>::try_from(files) {
+        Ok([baseline, candidate]) => Ok(Command::Compare(CompareOptions { baseline, candidate, threshold })),
+        Err(_) => Err("compare needs a baseline and a candidate results file".to_string()),
+    }
+}
+
+/// Parses the arguments that follow the program name
+pub fn parse_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("report") {
+        args.next();
+        return parse_report_args(args);
+    }
+    if args.peek().map(String::as_str) == Some("compare") {
+        args.next();
+        return parse_compare_args(args);
+    }
+
+    let mut options = Options::default();
+    let mut patterns = Vec::new();
+    let mut included: Option<Vec<&'static str>> = None;
+    let mut excluded = Vec::new();
+    let mut format = None;
+    let mut timeout = None;
+
+    while let Some(arg) = args.next() {
+        // Both `--option value` and `--option=value` are accepted
+        let (option, inline_value) = split_inline(&arg);
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for {}", option))
+        };
+        let number = |value: String| -> Result<usize, String> {
+            match value.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!("invalid value '{}' for {}, expected a positive number", value, option)),
+            }
+        };
+        let count = |value: String| -> Result<usize, String> {
+            value.parse().map_err(|_| format!("invalid value '{}' for {}, expected a number", value, option))
+        };
+
+        match option.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-s" | "--scales" => {
+                options.sizes = Sizes::Scales(parse_list(&option, &value()?, |scale| scale.parse().ok().filter(|&k| k <= MAX_SCALE))?)
+            }
+            "--sizes" => options.sizes = Sizes::Explicit(parse_list(&option, &value()?, parse_size)?),
+            "-i" | "--iterations" => options.iterations = number(value()?)?,
+            "-w" | "--warmup" => options.warmup = count(value()?)?,
+            "-r" | "--repetitions" => options.repetitions = number(value()?)?,
+            "-e" | "--engines" => included = Some(parse_list(&option, &value()?, parse_engine)?),
+            "-x" | "--exclude" => excluded.extend(parse_list(&option, &value()?, parse_engine)?),
+            "-t" | "--topology" => options.topology = Some(value()?.parse().map_err(|e| format!("{}", e))?),
+            "-o" | "--output" => options.output = Some(value()?),
+            "-f" | "--format" => format = Some(value()?.parse()?),
+            "--verify" => options.verify = true,
+            "--check-every" => options.check_every = number(value()?)?,
+            "--isolate" => options.isolate = true,
+            "--timeout" => timeout = Some(number(value()?)? as u64),
+            // Internal: how the parent of an isolated run starts each job
+            "--job" => options.job = true,
+            _ if option.starts_with('-') && option.len() > 1 => return Err(format!("unknown option '{}'", option)),
+            _ => patterns.push(arg),
+        }
+    }
+
+    if !patterns.is_empty() {
+        options.patterns = patterns;
+    }
+    if options.sizes.grids().is_empty() {
+        return Err("no grid sizes to run".to_string());
+    }
+    options.engines = included
+        .unwrap_or_else(|| ENGINES.to_vec())
+        .into_iter()
+        .filter(|engine| !excluded.contains(engine))
+        .collect();
+    if options.engines.is_empty() {
+        return Err("no engines left to run".to_string());
+    }
+
+    // Without --format the output extension decides
+    options.format = format
+        .or_else(|| {
+            let extension = Path::new(options.output.as_deref()?).extension()?.to_str()?;
+            extension.parse().ok()
+        })
+        .unwrap_or(OutputFormat::Csv);
+    if let Some(timeout) = timeout {
+        if !options.isolate {
+            return Err("--timeout needs --isolate".to_string());
+        }
+        options.timeout = timeout;
+    }
+    if options.job && (options.patterns.len() != 1 || options.sizes.grids().len() != 1 || options.engines.len() != 1) {
+        return Err("--job runs exactly one pattern, size and engine".to_string());
+    }
+    if options.patterns.len() > 1 && options.output.as_ref().is_some_and(|output| !output.contains("{pattern}")) {
+        return Err("--output needs a {pattern} placeholder when running several patterns".to_string());
+    }
+    Ok(Command::Run(options))
+}
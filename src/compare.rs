@@ -0,0 +1,129 @@
+//! Comparison of two results files: the speedup of every engine on every grid size the two
+//! have in common, and whether it is significant over the repetitions
+use wasm_game_of_life::bench::{Comparison, EngineResult};
+use wasm_game_of_life::utils::{ResultsFile, RunInfo};
+
+/// What a comparison says about the candidate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Faster,
+    Slower,     // Significantly, but within the threshold
+    Regression, // Significantly slower beyond the threshold
+    Unchanged,  // No significant difference
+    TooFewRuns, // Fewer than two repetitions on either side
+}
+
+impl Verdict {
+    fn of(comparison: &Comparison, threshold: f64) -> Verdict {
+        if comparison.baseline.samples < 2 || comparison.candidate.samples < 2 {
+            Verdict::TooFewRuns
+        } else if !comparison.significant {
+            Verdict::Unchanged
+        } else if comparison.speedup > 1.0 {
+            Verdict::Faster
+        } else if comparison.change * 100.0 > threshold {
+            Verdict::Regression
+        } else {
+            Verdict::Slower
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Verdict::Faster => "faster",
+            Verdict::Slower => "slower",
+            Verdict::Regression => "REGRESSION",
+            Verdict::Unchanged => "no significant change",
+            Verdict::TooFewRuns => "too few runs to tell",
+        }
+    }
+}
+
+/// Results of one engine on one grid size of one pattern
+fn key<'a>(info: &'a RunInfo, result: &'a EngineResult) -> (&'a str, &'a str, usize, usize) {
+    (&info.pattern, &result.engine, result.width, result.height)
+}
+
+/// Settings of the two runs that differ, which makes their times hard to compare
+fn differences(baseline: &RunInfo, candidate: &RunInfo) -> Vec<String> {
+    let mut differences = Vec::new();
+    let mut differ = |what: &str, baseline: String, candidate: String| {
+        if baseline != candidate {
+            differences.push(format!("{} {} -> {}", what, baseline, candidate));
+        }
+    };
+    differ("rule", baseline.rule.clone(), candidate.rule.clone());
+    differ("topology", baseline.topology.clone(), candidate.topology.clone());
+    differ("generations", baseline.iterations.to_string(), candidate.iterations.to_string());
+    differ("threads", baseline.threads.to_string(), candidate.threads.to_string());
+    differ("CPU", baseline.cpu_model.clone(), candidate.cpu_model.clone());
+    differences
+}
+
+/// Prints how every engine and grid size of `candidate` compares with `baseline`. Returns how
+/// many regressed beyond `threshold` percent, or an error if the files have nothing in common.
+pub fn compare_results(baseline: &ResultsFile, candidate: &ResultsFile, threshold: f64) -> Result<usize, String> {
+    let describe = |info: &RunInfo| format!("{} revision {} on {}, {}", info.pattern, info.git_revision, info.cpu_model, info.timestamp);
+    println!("Baseline:  {}", describe(&baseline.info));
+    println!("Candidate: {}", describe(&candidate.info));
+    for difference in differences(&baseline.info, &candidate.info) {
+        println!("Warning: the runs differ in {}", difference);
+    }
+
+    let mut matched = 0;
+    let mut regressions = 0;
+    println!();
+    println!(
+        "{:<16} {:>10} {:>14} {:>14} {:>9} {:>9}  Verdict",
+        "Engine", "Size", "Baseline ms", "Candidate ms", "Speedup", "Change"
+    );
+    for before in &baseline.results {
+        let after = candidate.results.iter().find(|after| key(&candidate.info, after) == key(&baseline.info, before));
+        let after = match after {
+            Some(after) if !before.generation_ns.is_empty() && !after.generation_ns.is_empty() => after,
+            _ => continue,
+        };
+        let comparison = Comparison::of(&before.totals(), &after.totals());
+        let verdict = Verdict::of(&comparison, threshold);
+        println!(
+            "{:<16} {:>10} {:>14.3} {:>14.3} {:>8.2}x {:>+8.1}%  {}",
+            before.engine,
+            before.size(),
+            comparison.baseline.mean / 1e6,
+            comparison.candidate.mean / 1e6,
+            comparison.speedup,
+            comparison.change * 100.0,
+            verdict.label()
+        );
+        matched += 1;
+        if verdict == Verdict::Regression {
+            regressions += 1;
+        }
+    }
+
+    // Engines and sizes run only once are worth knowing about, but cannot regress
+    let unmatched = |results: &ResultsFile, others: &ResultsFile| -> Vec<String> {
+        results
+            .results
+            .iter()
+            .filter(|result| !others.results.iter().any(|other| key(&others.info, other) == key(&results.info, result)))
+            .map(|result| format!("{} {}", result.engine, result.size()))
+            .collect()
+    };
+    for (side, only) in [("baseline", unmatched(baseline, candidate)), ("candidate", unmatched(candidate, baseline))] {
+        if !only.is_empty() {
+            println!("Only in the {}: {}", side, only.join(", "));
+        }
+    }
+
+    if matched == 0 {
+        return Err("The results files have no engine, pattern and grid size in common".to_string());
+    }
+    println!();
+    if regressions > 0 {
+        println!("{} of {} engine and grid size pairs regressed beyond {}%", regressions, matched, threshold);
+    } else {
+        println!("No regressions beyond {}% in {} engine and grid size pairs", threshold, matched);
+    }
+    Ok(regressions)
+}
@@ -1,21 +1,10 @@
 #![allow(dead_code)]
-extern crate cfg_if;
-extern crate wasm_bindgen;
-
-use cfg_if::cfg_if;
 use wasm_bindgen::prelude::*;
 use rayon::prelude::*; 
 use std::collections::{HashSet, HashMap};
-
-cfg_if! {
-    // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
-    // allocator.
-    if #[cfg(feature = "wee_alloc")] {
-        extern crate wee_alloc;
-        #[global_allocator]
-        static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
-    }
-}
+use crate::traits::LifeUniverse;
+use crate::rules::Rule;
+use crate::topology::Topology;
 
 #[wasm_bindgen]
 #[repr(u8)]
@@ -30,13 +19,15 @@ pub enum Cell {
 pub struct Universe {
     width: usize,
     height: usize,
-    live_cells: HashSet<(usize, usize)>,
+    live_cells: HashSet<(i64, i64)>, // Signed so an unbounded plane can grow past the viewport
+    rule: Rule,
+    topology: Topology,
 }
 
 // helper functions
 impl Universe {
-    fn get_neighbors(&self, row: usize, col: usize) -> Vec<(usize,  usize)> {
-        let deltas = [-1, 0, 1];
+    fn get_neighbors(&self, row: i64, col: i64) -> Vec<(i64, i64)> {
+        let deltas: [isize; 3] = [-1, 0, 1];
         let mut neighbors = Vec::new();
 
         for &delta_row in &deltas {
@@ -44,21 +35,60 @@ impl Universe {
                 if delta_row == 0 && delta_col == 0 {
                     continue;
                 }
-                let neighbor_row = ((self.height as i32 + row as i32 + delta_row)  % self.height as i32) as usize ;
-                let neighbor_col = ((self.width as i32  + col as i32 + delta_col) % self.width as i32) as usize;
-                neighbors.push((neighbor_row, neighbor_col));
+                if !self.topology.is_bounded() {
+                    neighbors.push((row + delta_row as i64, col + delta_col as i64));
+                } else if let Some((neighbor_row, neighbor_col)) =
+                    self.topology.neighbour(self.width, self.height, row as usize, col as usize, delta_row, delta_col)
+                {
+                    neighbors.push((neighbor_row as i64, neighbor_col as i64));
+                }
             }
         }
 
         neighbors
     }
 
-    fn rules(is_alive: bool, neighbor_count: usize) -> bool {
-        match (is_alive, neighbor_count) {
-            (true, 2) | (_, 3) => true, // Stays alive or comes to life
-            _ => false, // Dies
+    /// Whether `(row, col)` lies on the `width x height` grid
+    fn in_viewport(&self, (row, col): (i64, i64)) -> bool {
+        row >= 0 && col >= 0 && row < self.height as i64 && col < self.width as i64
+    }
+
+    /// Returns whether the cell at signed `(row, col)` is alive; off-grid cells
+    /// only exist on an unbounded plane
+    pub fn get_cell_at(&self, row: i64, col: i64) -> bool {
+        self.live_cells.contains(&(row, col))
+    }
+
+    /// Sets the cell at signed `(row, col)` to alive or dead
+    pub fn set_cell_at(&mut self, row: i64, col: i64, alive: bool) {
+        if alive {
+            self.live_cells.insert((row, col));
+        } else {
+            self.live_cells.remove(&(row, col));
         }
     }
+
+    /// Signed coordinates of every live cell, including any outside the viewport
+    pub fn all_live_cells(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.live_cells.iter().copied()
+    }
+
+    /// Number of live cells, including any outside the viewport
+    pub fn total_population(&self) -> u64 {
+        self.live_cells.len() as u64
+    }
+
+    /// Signed `(top, left, bottom, right)` bounds of the live cells, inclusive
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        self.live_cells
+            .par_iter()
+            .map(|&(row, col)| (row, col, row, col))
+            .reduce_with(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+    }
+
+    fn rules(&self, is_alive: bool, neighbor_count: usize) -> bool {
+        self.rule.next_state(is_alive, neighbor_count)
+    }
 }
 
 
@@ -69,7 +99,7 @@ impl Universe {
         let live_cells = &self.live_cells;
 
          // Count neighbors using parallel iteration
-        let neighbor_counts: HashMap<(usize, usize), usize> = live_cells
+        let neighbor_counts: HashMap<(i64, i64), usize> = live_cells
             .par_iter()
             .flat_map(|&(row, col)| self.get_neighbors(row, col))
             .fold(HashMap::new,
@@ -88,11 +118,11 @@ impl Universe {
             );
         
         // Compute next state in parallel
-        let next_state: HashSet<(usize, usize)> = neighbor_counts
+        let mut next_state: HashSet<(i64, i64)> = neighbor_counts
             .par_iter()
             .filter_map(|(&cell, &count)| {
                 let is_alive = live_cells.contains(&cell);
-                if Self::rules(is_alive, count) {
+                if self.rules(is_alive, count) {
                     Some(cell)
                 } else {
                     None
@@ -100,6 +130,16 @@ impl Universe {
             })
             .collect();
 
+        // Isolated live cells never show up in the counts, so S0 has to keep them explicitly
+        if self.rule.is_survival(0) {
+            next_state.par_extend(
+                live_cells
+                    .par_iter()
+                    .filter(|cell| !neighbor_counts.contains_key(cell))
+                    .copied(),
+            );
+        }
+
         self.live_cells = next_state;
 
 
@@ -135,8 +175,8 @@ impl Universe {
 
         for (index, &value) in flat_matrix.iter().enumerate() {
             if value == 1 {
-                let row = index / width;
-                let col = index % width;
+                let row = (index / width) as i64;
+                let col = (index % width) as i64;
                 live_cells.insert((row, col));
             }
         }
@@ -145,6 +185,8 @@ impl Universe {
             width,
             height,
             live_cells,
+            rule: Rule::default(),
+            topology: Topology::default(),
         }
     }
 
@@ -159,7 +201,7 @@ impl Universe {
         let mut buffer = String::new();
         for row in 0..self.height {
             for col in 0..self.width {
-                if self.live_cells.contains(&(row, col)) {
+                if self.live_cells.contains(&(row as i64, col as i64)) {
                     buffer.push('■');
                 } else {
                     buffer.push('□');
@@ -178,4 +220,91 @@ impl fmt::Display for Universe {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.render())
     }
-}
\ No newline at end of file
+}
+
+impl LifeUniverse for Universe {
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
+        Universe::new_with_matrix(width, height, flat_matrix)
+    }
+
+    fn from_live_cells(width: usize, height: usize, live_cells: Vec<(usize, usize)>) -> Universe {
+        Universe {
+            width,
+            height,
+            live_cells: live_cells.into_iter().map(|(row, col)| (row as i64, col as i64)).collect(),
+            rule: Rule::default(),
+            topology: Topology::default(),
+        }
+    }
+
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(self.supports_rule(&rule), "The hashed parallel engine does not support the rule {}", rule);
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn supports_topology(&self, _topology: Topology) -> bool {
+        true
+    }
+
+    fn set_topology(&mut self, topology: Topology) {
+        assert!(self.supports_topology(topology), "The hashed parallel engine does not support the {} topology", topology);
+        topology.check_size(self.width, self.height);
+        self.topology = topology;
+
+        // Cells that grew past the viewport of an unbounded plane do not exist on a bounded grid
+        if topology.is_bounded() {
+            let (width, height) = (self.width as i64, self.height as i64);
+            self.live_cells.retain(|&(row, col)| row >= 0 && col >= 0 && row < height && col < width);
+        }
+    }
+
+    fn tick(&mut self) {
+        Universe::tick(self)
+    }
+
+    fn get_cell(&self, row: usize, col: usize) -> bool {
+        self.get_cell_at(row as i64, col as i64)
+    }
+
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool) {
+        self.set_cell_at(row as i64, col as i64, alive)
+    }
+
+    fn population(&self) -> usize {
+        if self.topology.is_bounded() {
+            self.live_cells.len()
+        } else {
+            self.live_cells().count()
+        }
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        // On an unbounded plane only the cells inside the viewport are reported
+        Box::new(
+            self.live_cells
+                .iter()
+                .filter(move |&&cell| self.in_viewport(cell))
+                .map(|&(row, col)| (row as usize, col as usize)),
+        )
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn render(&self) -> String {
+        Universe::render(self)
+    }
+}
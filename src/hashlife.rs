@@ -1,187 +1,1163 @@
 use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem::size_of;
 use wasm_bindgen::prelude::*;
+use crate::traits::LifeUniverse;
+use crate::rules::{Rule, RuleError};
+use crate::topology::{Topology, TopologyError};
+use crate::utils::{parse_rle_runs, placement_offset, Placement, RleError};
 
 /// Enum representing the state of a cell
 #[wasm_bindgen]
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)] // ✅ Ensure Cell implements Hash
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Cell {
     Dead = 0,
     Alive = 1,
 }
 
-/// Struct representing a quadrant-based Hashlife Node
-#[derive(Clone, PartialEq, Eq, Debug)]
-struct Node {
-    size: usize,
-    nw: Option<Box<Node>>,
-    ne: Option<Box<Node>>,
-    sw: Option<Box<Node>>,
-    se: Option<Box<Node>>,
-    center: Option<Cell>, // Central cell for base case
-}
+/// Index of a node in the universe's node arena
+type NodeId = usize;
 
-impl Hash for Node {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.size.hash(state);
-        self.nw.as_ref().map(|n| n.size).hash(state);
-        self.ne.as_ref().map(|n| n.size).hash(state);
-        self.sw.as_ref().map(|n| n.size).hash(state);
-        self.se.as_ref().map(|n| n.size).hash(state);
-        self.center.hash(state);
-    }
+/// Canonical level-0 leaves, always stored first in the arena
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+/// Smallest root level; the base case of the recursion works on 4x4 (level 2) nodes
+/// and stepping needs at least one level of padding around them
+const MIN_LEVEL: u8 = 3;
+
+/// Largest root level: positions inside the root are `i64`, so its side `2^level` must fit
+const MAX_LEVEL: u8 = 62;
+
+/// Largest `k` of `step_pow2`: a jump of `2^k` generations needs a root of level `k + 3`
+pub const MAX_STEP_LOG2: u8 = MAX_LEVEL - 3;
+
+/// Default budget for the node arena, intern table and result cache
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 30;
+
+type InternKey = (NodeId, NodeId, NodeId, NodeId);
+type CacheKey = (NodeId, u8);
+
+/// Quadtree node covering a `2^level x 2^level` square.
+///
+/// Nodes are hash-consed: two nodes with the same children are the same `NodeId`,
+/// so identical regions anywhere in space and time share storage and results.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct Node {
+    level: u8,
+    nw: NodeId,
+    ne: NodeId,
+    sw: NodeId,
+    se: NodeId,
+    population: u64,
 }
 
-/// Universe struct for Hashlife implementation
+/// Universe struct for Hashlife implementation.
+///
+/// The quadtree lives on an unbounded plane, but the `width x height` grid the pattern
+/// was seeded into is bounded with its edges joined according to the topology, as in
+/// Golly: while the pattern is clear of the edges it is advanced in big memoized jumps,
+/// otherwise one generation at a time with the edges copied across and everything
+/// outside the grid cleared afterwards. On an unbounded plane the grid is only a
+/// viewport and the pattern always advances in jumps.
 #[wasm_bindgen]
 pub struct Universe {
-    cache: HashMap<Node, Node>,
-    root: Node,
+    width: usize,
+    height: usize,
+    nodes: Vec<Node>,                                        // Arena of interned nodes
+    interned: HashMap<InternKey, NodeId>,                    // Children -> canonical node
+    empty: Vec<NodeId>,                                      // Empty node for each level
+    cache: HashMap<CacheKey, NodeId>,                        // (node, log2 of step) -> RESULT
+    root: NodeId,
+    origin: (i64, i64),                                      // (row, col) of the root's top-left corner
+    generation: u64,                                         // Generations advanced since construction
+    rule: Rule,
+    topology: Topology,
+    memory_limit: usize,                                     // Bytes allowed before collecting garbage
+    cache_hits: u64,
+    cache_misses: u64,
+    collections: u64,
+}
+
+/// Memory and memoization statistics of a Hashlife universe
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HashlifeStats {
+    pub nodes: usize,         // Interned nodes in the arena
+    pub cache_entries: usize, // Memoized RESULTs
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub bytes_used: usize,    // Estimated bytes held by the arena and hash tables
+    pub collections: u64,     // Garbage collections run so far
+}
+
+impl HashlifeStats {
+    /// Fraction of RESULT lookups answered from the cache
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / lookups as f64
+        }
+    }
 }
 
 impl Universe {
     /// Creates a new universe from a flat matrix
     pub fn new_with_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
-        let root = Universe::build_tree(width, height, &flat_matrix);
-        Universe {
+        assert_eq!(flat_matrix.len(), width * height);
+
+        let live_cells = flat_matrix
+            .iter()
+            .enumerate()
+            .filter(|&(_, &value)| value == Cell::Alive as u8)
+            .map(|(idx, _)| ((idx / width) as i64, (idx % width) as i64));
+        Universe::from_cells(width, height, Topology::default(), live_cells)
+    }
+
+    /// Creates a universe from the signed `(row, col)` coordinates of its live cells. The
+    /// quadtree is built bottom-up from the cells alone, so seeding a grid of side 2^40
+    /// costs no more than a small one. Cells off the grid are dropped unless the topology
+    /// is unbounded.
+    pub fn from_cells<I>(width: usize, height: usize, topology: Topology, cells: I) -> Universe
+    where
+        I: IntoIterator<Item = (i64, i64)>,
+    {
+        topology.check_size(width, height);
+        let mut universe = Universe::empty_universe(width, height);
+        universe.topology = topology;
+        let cells: Vec<(i64, i64)> = cells.into_iter().collect();
+
+        // The root covers the grid and any cells beyond it, from the top-left-most of both
+        let top = cells.iter().map(|&(row, _)| row).min().unwrap_or(0).min(0);
+        let left = cells.iter().map(|&(_, col)| col).min().unwrap_or(0).min(0);
+        let bottom = cells.iter().map(|&(row, _)| row).max().unwrap_or(0).max(height as i64 - 1);
+        let right = cells.iter().map(|&(_, col)| col).max().unwrap_or(0).max(width as i64 - 1);
+        let level = Universe::level_for((bottom - top + 1).max(right - left + 1) as usize);
+
+        let offsets = cells.into_iter().map(|(row, col)| ((row - top) as u64, (col - left) as u64));
+        universe.root = universe.build_bottom_up(level, offsets);
+        universe.origin = (top, left);
+        if topology.is_bounded() {
+            universe.root = universe.clip(universe.root, top, left);
+        }
+        universe
+    }
+
+    /// Creates a universe from the text of a .rle file, with the pattern at `placement` in
+    /// the `width x height` grid. Live cells go straight from the runs into the quadtree,
+    /// never through a flat matrix. The rule and any bounded grid come from the header.
+    pub fn from_rle(text: &str, width: usize, height: usize, placement: Placement) -> Result<Universe, RleError> {
+        let mut cells = Vec::new();
+        let (pattern_width, pattern_height, metadata) = parse_rle_runs(text, &mut |row, col, length, state| {
+            // Two states only, so dying cells are dead
+            if state == Cell::Alive as u8 {
+                cells.extend((col..col + length).map(|col| (row, col)));
+            }
+        })?;
+        let (row_offset, col_offset) = placement_offset((pattern_width, pattern_height), (width, height), placement)?;
+
+        let live_cells = cells.into_iter().map(|(row, col)| ((row + row_offset) as i64, (col + col_offset) as i64));
+        let mut universe = Universe::from_cells(width, height, metadata.topology.unwrap_or_default(), live_cells);
+        if !universe.supports_rule(&metadata.rule) {
+            return Err(RleError::UnsupportedRule { rule: metadata.rule });
+        }
+        universe.rule = metadata.rule;
+        Ok(universe)
+    }
+
+    /// Level-`level` node holding the given live cells, relative to its top-left corner.
+    /// Each pass pairs up the nodes of one level into their parents, so the work is
+    /// proportional to the number of cells times the number of levels.
+    fn build_bottom_up<I>(&mut self, level: u8, cells: I) -> NodeId
+    where
+        I: IntoIterator<Item = (u64, u64)>,
+    {
+        let mut nodes: HashMap<(u64, u64), NodeId> = cells.into_iter().map(|cell| (cell, ALIVE)).collect();
+        for child_level in 0..level {
+            let empty = self.empty_node(child_level);
+            let mut parents: HashMap<(u64, u64), [NodeId; 4]> = HashMap::with_capacity(nodes.len());
+            for ((row, col), id) in nodes {
+                let quadrants = parents.entry((row / 2, col / 2)).or_insert([empty; 4]);
+                quadrants[(row % 2 * 2 + col % 2) as usize] = id;
+            }
+            nodes = parents
+                .into_iter()
+                .map(|(position, [nw, ne, sw, se])| (position, self.join(nw, ne, sw, se)))
+                .collect();
+        }
+        match nodes.get(&(0, 0)) {
+            Some(&root) => root,
+            None => self.empty_node(level),
+        }
+    }
+
+    /// Universe with no live cells whose root covers the `width x height` viewport
+    fn empty_universe(width: usize, height: usize) -> Universe {
+        let leaf = |population| Node { level: 0, nw: DEAD, ne: DEAD, sw: DEAD, se: DEAD, population };
+        let mut universe = Universe {
+            width,
+            height,
+            nodes: vec![leaf(0), leaf(1)],
+            interned: HashMap::new(),
+            empty: vec![DEAD],
             cache: HashMap::new(),
-            root,
+            root: DEAD,
+            origin: (0, 0),
+            generation: 0,
+            rule: Rule::default(),
+            topology: Topology::default(),
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            cache_hits: 0,
+            cache_misses: 0,
+            collections: 0,
+        };
+        let level = Universe::level_for(width.max(height));
+        universe.root = universe.empty_node(level);
+        universe
+    }
+
+    /// Smallest root level whose square covers `size` cells per side
+    fn level_for(size: usize) -> u8 {
+        let mut level = MIN_LEVEL;
+        while (1usize << level) < size {
+            level += 1;
         }
+        level
     }
 
-    /// Recursively builds a quadtree from the flat matrix
-    fn build_tree(width: usize, height: usize, flat_matrix: &[u8]) -> Node {
-        if width == 0 || height == 0 || flat_matrix.is_empty() {
-            return Node {
-                size: 1,
-                nw: None,
-                ne: None,
-                sw: None,
-                se: None,
-                center: Some(Cell::Dead), // Default to dead
-            };
+    /// Returns the canonical node with the given quadrants, creating it if needed
+    fn join(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        if let Some(&id) = self.interned.get(&(nw, ne, sw, se)) {
+            return id;
         }
 
-        if width == 1 && height == 1 {
-            let cell = if flat_matrix.first().copied().unwrap_or(0) == 1 { 
-                Cell::Alive 
-            } else { 
-                Cell::Dead 
-            };
-            return Node {
-                size: 1,
-                nw: None,
-                ne: None,
-                sw: None,
-                se: None,
-                center: Some(cell),
+        let population = [nw, ne, sw, se].iter().map(|&child| self.nodes[child].population).sum();
+        let node = Node {
+            level: self.nodes[nw].level + 1,
+            nw,
+            ne,
+            sw,
+            se,
+            population,
+        };
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.interned.insert((nw, ne, sw, se), id);
+        id
+    }
+
+    /// Canonical empty node of the given level
+    fn empty_node(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let below = *self.empty.last().unwrap();
+            let next = self.join(below, below, below, below);
+            self.empty.push(next);
+        }
+        self.empty[level as usize]
+    }
+
+    /// Central sub-node of half the size
+    fn centre(&mut self, id: NodeId) -> NodeId {
+        let node = self.nodes[id];
+        let (nw, ne, sw, se) = (self.nodes[node.nw], self.nodes[node.ne], self.nodes[node.sw], self.nodes[node.se]);
+        self.join(nw.se, ne.sw, sw.ne, se.nw)
+    }
+
+    /// True if every live cell of the node lies within its central half
+    fn is_padded(&self, id: NodeId) -> bool {
+        let node = self.nodes[id];
+        let centre_population = self.nodes[self.nodes[node.nw].se].population
+            + self.nodes[self.nodes[node.ne].sw].population
+            + self.nodes[self.nodes[node.sw].ne].population
+            + self.nodes[self.nodes[node.se].nw].population;
+        centre_population == node.population
+    }
+
+    /// Doubles the root, keeping the current contents in the centre
+    fn expand(&mut self) {
+        let root = self.nodes[self.root];
+        assert!(root.level < MAX_LEVEL, "The pattern has outgrown the 2^{0} x 2^{0} cells Hashlife can address", MAX_LEVEL);
+        let border = self.empty_node(root.level - 1);
+
+        let nw = self.join(border, border, border, root.nw);
+        let ne = self.join(border, border, root.ne, border);
+        let sw = self.join(border, root.sw, border, border);
+        let se = self.join(root.se, border, border, border);
+        self.root = self.join(nw, ne, sw, se);
+
+        let shift = 1i64 << (root.level - 1);
+        self.origin = (self.origin.0 - shift, self.origin.1 - shift);
+    }
+
+    /// Halves the root while all live cells stay in its centre, so empty space
+    /// gained by stepping does not accumulate
+    fn shrink(&mut self) {
+        while self.nodes[self.root].level > MIN_LEVEL && self.is_padded(self.root) {
+            let level = self.nodes[self.root].level;
+            self.root = self.centre(self.root);
+            let shift = 1i64 << (level - 2);
+            self.origin = (self.origin.0 + shift, self.origin.1 + shift);
+        }
+    }
+
+    /// Advances a level-2 node by one generation, returning its central 2x2 node
+    fn life_4x4(&mut self, id: NodeId) -> NodeId {
+        let mut grid = [[false; 4]; 4];
+        for (row, cells) in grid.iter_mut().enumerate() {
+            for (col, cell) in cells.iter_mut().enumerate() {
+                *cell = self.node_cell(id, row as u64, col as u64);
+            }
+        }
+
+        let mut next = [DEAD; 4];
+        for (i, (row, col)) in [(1, 1), (1, 2), (2, 1), (2, 2)].iter().enumerate() {
+            // Pack the 3x3 block around the cell into a rule table index
+            let neighbourhood = grid[row - 1..=row + 1]
+                .iter()
+                .flat_map(|cells| &cells[col - 1..=col + 1])
+                .enumerate()
+                .fold(0, |index, (bit, &cell)| index | (cell as usize) << bit);
+
+            next[i] = if self.rule.next_state_neighbourhood(neighbourhood) { ALIVE } else { DEAD };
+        }
+
+        self.join(next[0], next[1], next[2], next[3])
+    }
+
+    /// RESULT of a level-`k` node: its central level-`k-1` node advanced by `2^s` generations,
+    /// where `s` is `step_log2` capped at `k - 2`, the furthest a level-`k` node can see ahead.
+    /// The recursion asks sub-nodes for the whole step and gets as much of it as they can give.
+    fn successor(&mut self, id: NodeId, step_log2: u8) -> NodeId {
+        let node = self.nodes[id];
+        if node.population == 0 {
+            return self.empty_node(node.level - 1);
+        }
+        // Larger steps give the same result, so they share its cache entry
+        let step_log2 = step_log2.min(node.level - 2);
+        if let Some(&cached) = self.cache.get(&(id, step_log2)) {
+            self.cache_hits += 1;
+            return cached;
+        }
+        self.cache_misses += 1;
+
+        let result = if node.level == 2 {
+            self.life_4x4(id)
+        } else {
+            let (a, b, c, d) = (self.nodes[node.nw], self.nodes[node.ne], self.nodes[node.sw], self.nodes[node.se]);
+
+            // The nine overlapping level-(k-1) submacrocells
+            let n00 = node.nw;
+            let n01 = self.join(a.ne, b.nw, a.se, b.sw);
+            let n02 = node.ne;
+            let n10 = self.join(a.sw, a.se, c.nw, c.ne);
+            let n11 = self.join(a.se, b.sw, c.ne, d.nw);
+            let n12 = self.join(b.sw, b.se, d.nw, d.ne);
+            let n20 = node.sw;
+            let n21 = self.join(c.ne, d.nw, c.se, d.sw);
+            let n22 = node.se;
+
+            let mut c = [0; 9];
+            for (i, &sub) in [n00, n01, n02, n10, n11, n12, n20, n21, n22].iter().enumerate() {
+                c[i] = self.successor(sub, step_log2);
+            }
+
+            let quads = [(c[0], c[1], c[3], c[4]), (c[1], c[2], c[4], c[5]), (c[3], c[4], c[6], c[7]), (c[4], c[5], c[7], c[8])];
+            let mut result = [0; 4];
+            for (i, &(qnw, qne, qsw, qse)) in quads.iter().enumerate() {
+                result[i] = if step_log2 + 2 < node.level {
+                    // Already advanced by the full step, just take the centre
+                    let (qnw, qne, qsw, qse) = (self.nodes[qnw], self.nodes[qne], self.nodes[qsw], self.nodes[qse]);
+                    self.join(qnw.se, qne.sw, qsw.ne, qse.nw)
+                } else {
+                    // Second half of the 2^(k-2) step
+                    let quad = self.join(qnw, qne, qsw, qse);
+                    self.successor(quad, step_log2)
+                };
+            }
+            self.join(result[0], result[1], result[2], result[3])
+        };
+
+        self.cache.insert((id, step_log2), result);
+        result
+    }
+
+    /// Advances the whole plane by `2^step_log2` generations
+    fn advance(&mut self, step_log2: u8) {
+        // Pad until the pattern sits in the central half and the root is big enough
+        // for the step, then once more so growth during the step stays inside the result
+        while self.nodes[self.root].level < step_log2 + 2 || !self.is_padded(self.root) {
+            self.expand();
+        }
+        self.expand();
+
+        let level = self.nodes[self.root].level;
+        self.root = self.successor(self.root, step_log2);
+        let shift = 1i64 << (level - 2);
+        self.origin = (self.origin.0 + shift, self.origin.1 + shift);
+        self.generation = self.generation.checked_add(1 << step_log2).expect("The generation count overflowed");
+        self.shrink();
+
+        if self.bytes_used() > self.memory_limit {
+            self.collect_garbage();
+        }
+    }
+
+    /// Advances by `generations`, on a bounded grid jumping ahead only as far as the
+    /// pattern can grow without reaching the edges
+    fn advance_bounded(&mut self, generations: u64) {
+        if !self.topology.is_bounded() {
+            // One power-of-two jump per set bit, the highest bits as several of the largest jumps
+            for k in 0..MAX_STEP_LOG2 {
+                if generations & (1 << k) != 0 {
+                    self.advance(k);
+                }
+            }
+            for _ in 0..generations >> MAX_STEP_LOG2 {
+                self.advance(MAX_STEP_LOG2);
+            }
+            return;
+        }
+
+        let mut remaining = generations;
+        while remaining > 0 {
+            let Some((top, left, bottom, right)) = self.bounding_box() else {
+                // Without B0 an empty grid stays empty
+                self.generation += remaining;
+                return;
             };
+
+            // Cells spread at most one cell per generation, so for `margin` generations
+            // nothing reaches the edge rows and columns the topology joins together
+            let margin = (top - 1)
+                .min(left - 1)
+                .min(self.height as i64 - 2 - bottom)
+                .min(self.width as i64 - 2 - right);
+            if margin >= 1 {
+                let jump = remaining.min(margin as u64);
+                let step_log2 = (u64::BITS - 1 - jump.leading_zeros()) as u8;
+                self.advance(step_log2);
+                remaining -= 1 << step_log2;
+            } else {
+                self.join_edges();
+                self.advance(0);
+                self.root = self.clip(self.root, self.origin.0, self.origin.1);
+                remaining -= 1;
+            }
+        }
+    }
+
+    /// Copies the live edge cells into the one-cell ring around the grid where the
+    /// topology joins them, so the next generation sees them as neighbours
+    fn join_edges(&mut self) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let ring = (-1..=width).flat_map(|col| [(-1, col), (height, col)])
+            .chain((0..height).flat_map(|row| [(row, -1), (row, width)]));
+
+        let mut copies = Vec::new();
+        for (row, col) in ring {
+            if let Some((src_row, src_col)) = self.topology.wrap(self.width, self.height, row, col) {
+                if self.get_absolute(src_row as i64, src_col as i64) {
+                    copies.push((row as i64, col as i64));
+                }
+            }
+        }
+        for (row, col) in copies {
+            self.set_absolute(row, col, true);
+        }
+    }
+
+    /// Returns the node at absolute `(top, left)` with every cell outside the grid removed
+    fn clip(&mut self, id: NodeId, top: i64, left: i64) -> NodeId {
+        let node = self.nodes[id];
+        let size = 1i64 << node.level;
+        if node.population == 0
+            || (top >= 0 && left >= 0 && top + size <= self.height as i64 && left + size <= self.width as i64)
+        {
+            return id;
         }
+        if top >= self.height as i64 || left >= self.width as i64 || top + size <= 0 || left + size <= 0 {
+            return self.empty_node(node.level);
+        }
+
+        let half = size / 2;
+        let nw = self.clip(node.nw, top, left);
+        let ne = self.clip(node.ne, top, left + half);
+        let sw = self.clip(node.sw, top + half, left);
+        let se = self.clip(node.se, top + half, left + half);
+        self.join(nw, ne, sw, se)
+    }
+
+    /// Smallest (or with `max`, largest) row (or with `cols`, column) holding a live
+    /// cell, relative to the node's top-left corner
+    fn extent(&self, id: NodeId, cols: bool, max: bool) -> Option<u64> {
+        let node = self.nodes[id];
+        if node.population == 0 {
+            return None;
+        }
+        if node.level == 0 {
+            return Some(0);
+        }
+
+        let half = 1u64 << (node.level - 1);
+        // Children sharing the first and second half along the axis
+        let (first, second) = if cols {
+            ([node.nw, node.sw], [node.ne, node.se])
+        } else {
+            ([node.nw, node.ne], [node.sw, node.se])
+        };
+        let (near, far, offset) = if max { (second, first, (half, 0)) } else { (first, second, (0, half)) };
 
-        let half_width = width / 2;
-        let half_height = height / 2;
-        
-        // Ensure there is enough data
-        if flat_matrix.len() < width * height {
-            return Node {
-                size: width,
-                nw: None,
-                ne: None,
-                sw: None,
-                se: None,
-                center: None, // Return minimal node
+        let pick = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (Some(a), Some(b)) => Some(if max { a.max(b) } else { a.min(b) }),
+            (a, b) => a.or(b),
+        };
+        let near_extent = pick(self.extent(near[0], cols, max), self.extent(near[1], cols, max));
+        match near_extent {
+            Some(extent) => Some(extent + offset.0),
+            None => pick(self.extent(far[0], cols, max), self.extent(far[1], cols, max)).map(|extent| extent + offset.1),
+        }
+    }
+
+    /// Absolute `(top, left, bottom, right)` bounds of the live cells, inclusive
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        let top = self.extent(self.root, false, false)? as i64;
+        let left = self.extent(self.root, true, false)? as i64;
+        let bottom = self.extent(self.root, false, true)? as i64;
+        let right = self.extent(self.root, true, true)? as i64;
+        Some((self.origin.0 + top, self.origin.1 + left, self.origin.0 + bottom, self.origin.1 + right))
+    }
+
+    /// Estimated bytes held by the node arena, the intern table and the result cache
+    fn bytes_used(&self) -> usize {
+        // hashbrown keeps one control byte per bucket next to each key/value pair
+        self.nodes.capacity() * size_of::<Node>()
+            + self.interned.capacity() * (size_of::<InternKey>() + size_of::<NodeId>() + 1)
+            + self.cache.capacity() * (size_of::<CacheKey>() + size_of::<NodeId>() + 1)
+    }
+
+    /// Mark-and-sweep collection of nodes unreachable from the root.
+    ///
+    /// Surviving nodes are compacted to the front of the arena and the intern table
+    /// is rebuilt; cached RESULTs are kept only when both the node and its result survive.
+    /// Collection runs between steps, so the limit can be exceeded during a single jump.
+    pub fn collect_garbage(&mut self) {
+        let mut marked = vec![false; self.nodes.len()];
+        marked[DEAD] = true;
+        marked[ALIVE] = true;
+        marked[self.root] = true;
+        for &empty in &self.empty {
+            marked[empty] = true;
+        }
+
+        // Children are always created before their parents, so one descending sweep marks everything
+        for id in (0..self.nodes.len()).rev() {
+            if marked[id] && self.nodes[id].level > 0 {
+                let node = self.nodes[id];
+                for child in [node.nw, node.ne, node.sw, node.se] {
+                    marked[child] = true;
+                }
+            }
+        }
+
+        let mut remap = vec![0; self.nodes.len()];
+        let mut nodes = Vec::with_capacity(marked.iter().filter(|&&m| m).count());
+        let mut interned = HashMap::new();
+        for (id, node) in self.nodes.iter().enumerate() {
+            if !marked[id] {
+                continue;
+            }
+            let mut node = *node;
+            if node.level > 0 {
+                node.nw = remap[node.nw];
+                node.ne = remap[node.ne];
+                node.sw = remap[node.sw];
+                node.se = remap[node.se];
+                interned.insert((node.nw, node.ne, node.sw, node.se), nodes.len());
+            }
+            remap[id] = nodes.len();
+            nodes.push(node);
+        }
+
+        self.cache = self
+            .cache
+            .iter()
+            .filter(|&(&(id, _), &result)| marked[id] && marked[result])
+            .map(|(&(id, step_log2), &result)| ((remap[id], step_log2), remap[result]))
+            .collect();
+        self.nodes = nodes;
+        self.interned = interned;
+        self.empty = self.empty.iter().map(|&id| remap[id]).collect();
+        self.root = remap[self.root];
+        self.collections += 1;
+    }
+
+    /// Sets the memory budget in bytes; exceeding it triggers garbage collection
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.memory_limit = bytes;
+    }
+
+    pub fn memory_limit(&self) -> usize {
+        self.memory_limit
+    }
+
+    /// Current node, cache and memory statistics
+    pub fn stats(&self) -> HashlifeStats {
+        HashlifeStats {
+            nodes: self.nodes.len(),
+            cache_entries: self.cache.len(),
+            cache_hits: self.cache_hits,
+            cache_misses: self.cache_misses,
+            bytes_used: self.bytes_used(),
+            collections: self.collections,
+        }
+    }
+
+    /// State of the cell at `(row, col)` relative to the node's top-left corner
+    fn node_cell(&self, id: NodeId, row: u64, col: u64) -> bool {
+        let mut node = self.nodes[id];
+        let (mut row, mut col) = (row, col);
+        while node.level > 0 {
+            if node.population == 0 {
+                return false;
+            }
+            let half = 1u64 << (node.level - 1);
+            let child = match (row < half, col < half) {
+                (true, true) => node.nw,
+                (true, false) => node.ne,
+                (false, true) => node.sw,
+                (false, false) => node.se,
             };
+            row %= half;
+            col %= half;
+            node = self.nodes[child];
         }
+        node.population == 1
+    }
 
-        // Create quadrants by extracting rows from the flat matrix
-        let mut nw_matrix = Vec::new();
-        let mut ne_matrix = Vec::new();
-        let mut sw_matrix = Vec::new();
-        let mut se_matrix = Vec::new();
+    /// Returns `id` with the cell at `(row, col)` (relative to its corner) set
+    fn set_node_cell(&mut self, id: NodeId, row: u64, col: u64, alive: bool) -> NodeId {
+        let node = self.nodes[id];
+        if node.level == 0 {
+            return if alive { ALIVE } else { DEAD };
+        }
 
-        for row in 0..half_height {
-            let start = row * width;
-            let mid = start + half_width;
-            let end = start + width;
+        let half = 1u64 << (node.level - 1);
+        let (mut nw, mut ne, mut sw, mut se) = (node.nw, node.ne, node.sw, node.se);
+        match (row < half, col < half) {
+            (true, true) => nw = self.set_node_cell(nw, row, col, alive),
+            (true, false) => ne = self.set_node_cell(ne, row, col - half, alive),
+            (false, true) => sw = self.set_node_cell(sw, row - half, col, alive),
+            (false, false) => se = self.set_node_cell(se, row - half, col - half, alive),
+        }
+        self.join(nw, ne, sw, se)
+    }
 
-            nw_matrix.extend_from_slice(&flat_matrix[start..mid]);  // Top-left
-            ne_matrix.extend_from_slice(&flat_matrix[mid..end]);    // Top-right
+    /// Root-relative coordinates of an absolute cell, if the root covers it
+    fn root_offset(&self, row: i64, col: i64) -> Option<(u64, u64)> {
+        let size = 1i64 << self.nodes[self.root].level;
+        let (row, col) = (row - self.origin.0, col - self.origin.1);
+        if row < 0 || col < 0 || row >= size || col >= size {
+            None
+        } else {
+            Some((row as u64, col as u64))
         }
+    }
 
-        for row in half_height..height {
-            let start = row * width;
-            let mid = start + half_width;
-            let end = start + width;
+    /// State of the cell at absolute `(row, col)`
+    fn get_absolute(&self, row: i64, col: i64) -> bool {
+        match self.root_offset(row, col) {
+            Some((row, col)) => self.node_cell(self.root, row, col),
+            None => false,
+        }
+    }
 
-            sw_matrix.extend_from_slice(&flat_matrix[start..mid]);  // Bottom-left
-            se_matrix.extend_from_slice(&flat_matrix[mid..end]);    // Bottom-right
+    /// Sets the cell at absolute `(row, col)`, growing the root until it covers it
+    fn set_absolute(&mut self, row: i64, col: i64, alive: bool) {
+        while self.root_offset(row, col).is_none() {
+            self.expand();
         }
+        let (row, col) = self.root_offset(row, col).unwrap();
+        self.root = self.set_node_cell(self.root, row, col, alive);
+    }
 
-        // Recursively build the quad-tree
-        let nw = Universe::build_tree(half_width, half_height, &nw_matrix);
-        let ne = Universe::build_tree(half_width, half_height, &ne_matrix);
-        let sw = Universe::build_tree(half_width, half_height, &sw_matrix);
-        let se = Universe::build_tree(half_width, half_height, &se_matrix);
+    /// Visits the live cells of the node at absolute `(top, left)` that fall inside the viewport
+    fn visit_viewport<F>(&self, id: NodeId, top: i64, left: i64, func: &mut F)
+    where
+        F: FnMut(usize, usize),
+    {
+        let node = self.nodes[id];
+        let size = 1i64 << node.level;
+        if node.population == 0
+            || top >= self.height as i64
+            || left >= self.width as i64
+            || top + size <= 0
+            || left + size <= 0
+        {
+            return;
+        }
 
-        Node {
-            size: width,
-            nw: Some(Box::new(nw)),
-            ne: Some(Box::new(ne)),
-            sw: Some(Box::new(sw)),
-            se: Some(Box::new(se)),
-            center: None,
+        if node.level == 0 {
+            func(top as usize, left as usize);
+            return;
         }
+
+        let half = size / 2;
+        self.visit_viewport(node.nw, top, left, func);
+        self.visit_viewport(node.ne, top, left + half, func);
+        self.visit_viewport(node.sw, top + half, left, func);
+        self.visit_viewport(node.se, top + half, left + half, func);
     }
 
     /// Advances the universe by one tick using Hashlife
     pub fn tick(&mut self) {
-        let new_root = self.compute_next(self.root.clone()); // ✅ FIX: Clone before passing
-        self.root = new_root;
-    }
-
-    /// Computes the next state of the universe using memoized Hashlife
-    fn compute_next(&mut self, node: Node) -> Node {
-        if let Some(cached) = self.cache.get(&node) {
-            return cached.clone();
-        }
-
-        if node.size == 1 {
-            return Node {
-                size: 1,
-                nw: None,
-                ne: None,
-                sw: None,
-                se: None,
-                center: Some(match node.center {
-                    Some(Cell::Alive) => Cell::Dead,
-                    _ => Cell::Alive,
-                }),
-            };
+        self.advance_bounded(1);
+    }
+
+    /// Advances the universe by `2^k` generations, in a single memoized step unless
+    /// the pattern reaches the edges of the grid. Panics if `k` exceeds `MAX_STEP_LOG2`.
+    pub fn step_pow2(&mut self, k: u8) {
+        assert!(k <= MAX_STEP_LOG2, "Cannot step by 2^{} generations, at most 2^{}", k, MAX_STEP_LOG2);
+        self.advance_bounded(1 << k);
+    }
+
+    /// Advances the universe by `generations`, in power-of-two jumps while the
+    /// pattern stays clear of the edges of the grid
+    pub fn step(&mut self, generations: u64) {
+        self.advance_bounded(generations);
+    }
+
+    /// Runs multiple iterations using Hashlife
+    pub fn run_iterations(&mut self, iterations: usize) {
+        self.step(iterations as u64);
+    }
+
+    /// Number of generations advanced since the universe was created
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Number of live cells on the whole plane, including any outside the viewport
+    pub fn total_population(&self) -> u64 {
+        self.nodes[self.root].population
+    }
+}
+
+/// Reasons a macrocell file can be rejected. Lines are 1-based.
+#[derive(Debug)]
+pub enum MacrocellError {
+    /// The file could not be opened or read
+    Io(io::Error),
+    /// The first line is not `[M2] ...`
+    MissingHeader,
+    /// A `#R` rule that `Rule::parse` rejects
+    InvalidRule { line: usize, error: RuleError },
+    /// A `#R` rule Hashlife cannot evolve, i.e. one with dying states
+    UnsupportedRule { line: usize, rule: Rule },
+    /// A `#R` grid suffix that is not a Golly grid we can model, e.g. a shifted torus
+    InvalidTopology { line: usize, error: TopologyError },
+    /// A `#R` grid suffix such as `:T64,64` whose size is missing or does not suit it
+    InvalidGrid { line: usize, spec: String },
+    /// A node line that is neither an 8x8 leaf nor `level nw ne sw se`
+    InvalidNode { line: usize, message: String },
+}
+
+impl fmt::Display for MacrocellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacrocellError::Io(e) => write!(f, "{}", e),
+            MacrocellError::MissingHeader => write!(f, "missing '[M2]' header line"),
+            MacrocellError::InvalidRule { line, error } => write!(f, "line {}: {}", line, error),
+            MacrocellError::UnsupportedRule { line, rule } => {
+                write!(f, "line {}: the Hashlife engine does not support the rule {}", line, rule)
+            }
+            MacrocellError::InvalidTopology { line, error } => write!(f, "line {}: {}", line, error),
+            MacrocellError::InvalidGrid { line, spec } => {
+                write!(f, "line {}: invalid grid '{}', expected e.g. T64,64", line, spec)
+            }
+            MacrocellError::InvalidNode { line, message } => write!(f, "line {}: {}", line, message),
         }
+    }
+}
 
-        let nw = self.compute_next(*node.nw.clone().unwrap());
-        let ne = self.compute_next(*node.ne.clone().unwrap());
-        let sw = self.compute_next(*node.sw.clone().unwrap());
-        let se = self.compute_next(*node.se.clone().unwrap());
+impl std::error::Error for MacrocellError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MacrocellError::Io(e) => Some(e),
+            MacrocellError::InvalidRule { error, .. } => Some(error),
+            MacrocellError::InvalidTopology { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
 
-        let next_node = Node {
-            size: node.size,
-            nw: Some(Box::new(nw)),
-            ne: Some(Box::new(ne)),
-            sw: Some(Box::new(sw)),
-            se: Some(Box::new(se)),
-            center: None,
+impl From<io::Error> for MacrocellError {
+    fn from(e: io::Error) -> MacrocellError {
+        MacrocellError::Io(e)
+    }
+}
+
+/// Comment line recording the viewport of an unbounded plane, which Golly has no syntax for
+const VIEWPORT_COMMENT: &str = "#C viewport ";
+
+/// Golly's macrocell format: the quadtree itself, one line per distinct non-empty node
+/// of level 3 or more, children first. Level-3 nodes are 8x8 leaves written as rows of
+/// `.` and `*` ending in `$`; larger ones as `level nw ne sw se`, where children are
+/// 1-based line numbers among the nodes and 0 is the empty node. The last node is the
+/// root, centred on cell (0, 0), and a bounded grid is centred on it too.
+impl Universe {
+    /// Builds a universe from the text of a macrocell file. The viewport is the bounded
+    /// grid of the `#R` rule if any, else an unbounded plane.
+    pub fn from_macrocell(text: &str) -> Result<Universe, MacrocellError> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+        match lines.next() {
+            Some((_, line)) if line.starts_with("[M2]") => {}
+            _ => return Err(MacrocellError::MissingHeader),
+        }
+
+        let mut rule = Rule::default();
+        let mut grid = None; // (topology, width, height)
+        let mut viewport = None;
+        let mut generation = 0;
+        let mut universe = Universe::empty_universe(1, 1);
+        let mut ids: Vec<NodeId> = Vec::new();
+
+        for (line_number, line) in lines {
+            let invalid = |message: String| MacrocellError::InvalidNode { line: line_number, message };
+            if let Some(spec) = line.strip_prefix("#R") {
+                let (rule_spec, grid_spec) = match spec.split_once(':') {
+                    Some((rule_spec, grid_spec)) => (rule_spec, Some(grid_spec.trim())),
+                    None => (spec, None),
+                };
+                rule = Rule::parse(rule_spec.trim())
+                    .map_err(|error| MacrocellError::InvalidRule { line: line_number, error })?;
+                if rule.is_generations() {
+                    return Err(MacrocellError::UnsupportedRule { line: line_number, rule });
+                }
+                grid = match grid_spec {
+                    Some(grid_spec) => {
+                        let invalid_grid = || MacrocellError::InvalidGrid { line: line_number, spec: grid_spec.to_string() };
+                        let topology = Topology::from_golly(grid_spec)
+                            .map_err(|error| MacrocellError::InvalidTopology { line: line_number, error })?;
+                        let (width, height) = Topology::golly_grid_size(grid_spec).ok_or_else(invalid_grid)?;
+                        if !topology.supports_size(width, height) {
+                            return Err(invalid_grid());
+                        }
+                        Some((topology, width, height))
+                    }
+                    None => None,
+                };
+            } else if let Some(generations) = line.strip_prefix("#G") {
+                generation = generations.trim().parse().map_err(|_| invalid(format!("invalid generation '{}'", generations.trim())))?;
+            } else if let Some(size) = line.strip_prefix(VIEWPORT_COMMENT) {
+                viewport = size.split_once('x').and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+            } else if line.starts_with('#') || line.is_empty() {
+                continue;
+            } else if line.starts_with(['.', '*', '$']) {
+                ids.push(universe.parse_leaf(line).map_err(invalid)?);
+            } else {
+                let numbers: Vec<usize> = line
+                    .split_whitespace()
+                    .map(|value| value.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid(format!("invalid node '{}', expected 'level nw ne sw se'", line)))?;
+                let [level, nw, ne, sw, se] = numbers[..] else {
+                    return Err(invalid(format!("invalid node '{}', expected 'level nw ne sw se'", line)));
+                };
+                if !(4..64).contains(&level) {
+                    return Err(invalid(format!("invalid node '{}', expected 'level nw ne sw se'", line)));
+                }
+                let mut quadrants = [DEAD; 4];
+                for (quadrant, &child) in quadrants.iter_mut().zip(&[nw, ne, sw, se]) {
+                    *quadrant = match child {
+                        0 => universe.empty_node(level as u8 - 1),
+                        _ if child <= ids.len() && universe.nodes[ids[child - 1]].level as usize == level - 1 => ids[child - 1],
+                        _ => return Err(invalid(format!("node {} is not a level {} node defined earlier", child, level - 1))),
+                    };
+                }
+                ids.push(universe.join(quadrants[0], quadrants[1], quadrants[2], quadrants[3]));
+            }
+        }
+
+        let (topology, width, height) = match grid {
+            Some(grid) => grid,
+            None => {
+                let (width, height) = viewport.unwrap_or((1 << MIN_LEVEL, 1 << MIN_LEVEL));
+                (Topology::Unbounded, width, height)
+            }
         };
+        universe.width = width;
+        universe.height = height;
+        universe.rule = rule;
+        universe.topology = topology;
+        universe.generation = generation;
+        if let Some(&root) = ids.last() {
+            let radius = 1i64 << (universe.nodes[root].level - 1);
+            universe.root = root;
+            universe.origin = ((height / 2) as i64 - radius, (width / 2) as i64 - radius);
+        } else {
+            universe.root = universe.empty_node(Universe::level_for(width.max(height)));
+        }
 
-        self.cache.insert(node, next_node.clone());
-        next_node
+        // The root may be smaller than the grid, which other code expects it to cover
+        while universe.root_offset(0, 0).is_none() || universe.root_offset(height as i64 - 1, width as i64 - 1).is_none() {
+            universe.expand();
+        }
+        if topology.is_bounded() {
+            universe.root = universe.clip(universe.root, universe.origin.0, universe.origin.1);
+        }
+        Ok(universe)
     }
 
-    /// Runs multiple iterations using Hashlife
-    pub fn run_iterations(&mut self, iterations: usize) {
-        for _ in 0..iterations {
-            self.tick();
+    /// Reads a universe from a macrocell (.mc) file
+    pub fn read_macrocell(file_path: &str) -> Result<Universe, MacrocellError> {
+        Universe::from_macrocell(&fs::read_to_string(file_path)?)
+    }
+
+    /// Serializes the whole plane as macrocell text, centred on the middle of the grid.
+    /// Takes `&mut self` because re-centring the tree interns new nodes.
+    pub fn to_macrocell(&mut self) -> String {
+        let root = self.centred_root((self.height / 2) as i64, (self.width / 2) as i64);
+
+        let mut text = "[M2] (wasm_game_of_life)\n".to_string();
+        text.push_str(&format!("#R {}", self.rule));
+        match self.topology.to_golly(self.width, self.height) {
+            Some(grid) => text.push_str(&format!(":{}\n", grid)),
+            None => text.push_str(&format!("\n{}{}x{}\n", VIEWPORT_COMMENT, self.width, self.height)),
+        }
+        if self.generation > 0 {
+            text.push_str(&format!("#G {}\n", self.generation));
+        }
+
+        let mut lines = Vec::new();
+        if self.write_node(root, &mut HashMap::new(), &mut lines) == 0 {
+            lines.push("$".to_string()); // An empty leaf, so there is still a root
+        }
+        for line in lines {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Saves the whole plane to a macrocell (.mc) file
+    pub fn write_macrocell(&mut self, file_path: &str) -> io::Result<()> {
+        let text = self.to_macrocell();
+        fs::write(file_path, text)
+    }
+
+    /// Parses an 8x8 leaf line such as `.*$..*$***$` into a level-3 node
+    fn parse_leaf(&mut self, line: &str) -> Result<NodeId, String> {
+        let mut node = self.empty_node(3);
+        let (mut row, mut col) = (0, 0);
+        for c in line.chars() {
+            match c {
+                '.' => col += 1,
+                '*' if row < 8 && col < 8 => {
+                    node = self.set_node_cell(node, row, col, true);
+                    col += 1;
+                }
+                '$' => {
+                    row += 1;
+                    col = 0;
+                }
+                '*' => return Err(format!("leaf '{}' does not fit 8x8 cells", line)),
+                _ => return Err(format!("unexpected character '{}' in leaf, expected ., * or $", c)),
+            }
+        }
+        Ok(node)
+    }
+
+    /// Appends the macrocell lines of `id` and its descendants not written yet, returning
+    /// its 1-based line number among the nodes, or 0 if it is empty
+    fn write_node(&self, id: NodeId, written: &mut HashMap<NodeId, usize>, lines: &mut Vec<String>) -> usize {
+        let node = self.nodes[id];
+        if node.population == 0 {
+            return 0;
+        }
+        if let Some(&index) = written.get(&id) {
+            return index;
+        }
+
+        let line = if node.level == 3 {
+            let mut leaf = String::new();
+            for row in 0..8 {
+                let cells: String = (0..8).map(|col| if self.node_cell(id, row, col) { '*' } else { '.' }).collect();
+                leaf.push_str(cells.trim_end_matches('.'));
+                leaf.push('$');
+            }
+            // Empty rows at the bottom are implied
+            leaf.truncate(leaf.trim_end_matches('$').len() + 1);
+            leaf
+        } else {
+            let children = [node.nw, node.ne, node.sw, node.se].map(|child| self.write_node(child, written, lines));
+            format!("{} {} {} {} {}", node.level, children[0], children[1], children[2], children[3])
+        };
+        lines.push(line);
+        written.insert(id, lines.len());
+        lines.len()
+    }
+
+    /// Node of level 3 or more holding every live cell, centred on the absolute cell
+    /// `(row, col)` as macrocell roots are. The universe itself is left unchanged.
+    fn centred_root(&mut self, row: i64, col: i64) -> NodeId {
+        let (root, origin) = (self.root, self.origin);
+        let mut memo = HashMap::new();
+        let centred = loop {
+            let level = self.nodes[self.root].level;
+            let radius = 1i64 << (level - 2);
+            let (top, left) = (row - radius - self.origin.0, col - radius - self.origin.1);
+            let limit = 1i64 << (level - 1);
+            if level > MIN_LEVEL && (0..=limit).contains(&top) && (0..=limit).contains(&left) {
+                let window = self.window(self.root, top as u64, left as u64, &mut memo);
+                if self.nodes[window].population == self.nodes[self.root].population {
+                    break window;
+                }
+            }
+            self.expand();
+        };
+        self.root = root;
+        self.origin = origin;
+        centred
+    }
+
+    /// Level-`k-1` node whose top-left corner is at `(row, col)` inside the level-`k`
+    /// node `id`, where `row, col <= 2^(k-1)`. Built from the grandchildren it overlaps,
+    /// so shifting a tree by any offset costs time in its distinct nodes, not its area.
+    fn window(&mut self, id: NodeId, row: u64, col: u64, memo: &mut HashMap<(NodeId, u64, u64), NodeId>) -> NodeId {
+        let node = self.nodes[id];
+        let half = 1u64 << (node.level - 1);
+        if node.population == 0 {
+            return self.empty_node(node.level - 1);
+        }
+        // Aligned windows are children; at level 1 every window is
+        match (row == half, col == half) {
+            _ if row == 0 && col == 0 => return node.nw,
+            (false, true) if row == 0 => return node.ne,
+            (true, false) if col == 0 => return node.sw,
+            (true, true) => return node.se,
+            _ => {}
+        }
+        if let Some(&cached) = memo.get(&(id, row, col)) {
+            return cached;
+        }
+
+        let [nw, ne, sw, se] = [node.nw, node.ne, node.sw, node.se].map(|child| self.nodes[child]);
+        let grid = [
+            [nw.nw, nw.ne, ne.nw, ne.ne],
+            [nw.sw, nw.se, ne.sw, ne.se],
+            [sw.nw, sw.ne, se.nw, se.ne],
+            [sw.sw, sw.se, se.sw, se.se],
+        ];
+        let quarter = half / 2;
+        let mut quadrants = [DEAD; 4];
+        for (i, quadrant) in quadrants.iter_mut().enumerate() {
+            let top = row + (i as u64 / 2) * quarter;
+            let left = col + (i as u64 % 2) * quarter;
+            // The 2x2 block of grandchildren holding this quadrant
+            let (r, c) = ((top / quarter).min(2) as usize, (left / quarter).min(2) as usize);
+            let block = self.join(grid[r][c], grid[r][c + 1], grid[r + 1][c], grid[r + 1][c + 1]);
+            *quadrant = self.window(block, top - r as u64 * quarter, left - c as u64 * quarter, memo);
+        }
+        let result = self.join(quadrants[0], quadrants[1], quadrants[2], quadrants[3]);
+        memo.insert((id, row, col), result);
+        result
+    }
+}
+
+impl LifeUniverse for Universe {
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
+        Universe::new_with_matrix(width, height, flat_matrix)
+    }
+
+    fn from_live_cells(width: usize, height: usize, live_cells: Vec<(usize, usize)>) -> Universe {
+        let live_cells = live_cells.into_iter().map(|(row, col)| (row as i64, col as i64));
+        Universe::from_cells(width, height, Topology::default(), live_cells)
+    }
+
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn supports_rule(&self, rule: &Rule) -> bool {
+        // Leaves are single bits, so there is no room for dying states
+        !rule.is_generations()
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(self.supports_rule(&rule), "The Hashlife engine does not support the rule {}", rule);
+        // Cached RESULTs were computed under the old rule
+        self.cache.clear();
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn supports_topology(&self, _topology: Topology) -> bool {
+        true
+    }
+
+    fn set_topology(&mut self, topology: Topology) {
+        assert!(self.supports_topology(topology), "The Hashlife engine does not support the {} topology", topology);
+        // RESULTs only depend on the rule, so the cache stays valid
+        topology.check_size(self.width, self.height);
+        self.topology = topology;
+
+        // Cells that grew past the viewport of an unbounded plane do not exist on a bounded grid
+        if topology.is_bounded() {
+            self.root = self.clip(self.root, self.origin.0, self.origin.1);
+        }
+    }
+
+    fn tick(&mut self) {
+        Universe::tick(self)
+    }
+
+    fn step(&mut self, n: usize) {
+        Universe::step(self, n as u64)
+    }
+
+    fn get_cell(&self, row: usize, col: usize) -> bool {
+        self.get_absolute(row as i64, col as i64)
+    }
+
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool) {
+        self.set_absolute(row as i64, col as i64, alive);
+    }
+
+    fn population(&self) -> usize {
+        let mut count = 0;
+        self.visit_viewport(self.root, self.origin.0, self.origin.1, &mut |_, _| count += 1);
+        count
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        let mut cells = Vec::new();
+        self.visit_viewport(self.root, self.origin.0, self.origin.1, &mut |row, col| cells.push((row, col)));
+        Box::new(cells.into_iter())
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn render(&self) -> String {
+        let mut buffer = String::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                buffer.push(if self.get_cell(row, col) { '■' } else { '□' });
+            }
+            buffer.push('\n');
         }
+        buffer
     }
 }
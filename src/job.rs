@@ -0,0 +1,179 @@
+//! Benchmark jobs in child processes: one engine on one grid size of one pattern per process,
+//! so that the heap and thread pools of one engine cannot affect the next one's numbers.
+//!
+//! The child reports on stdout, one record per line with tab-separated fields:
+//!
+//! ```text
+//! job-result 1
+//! engine  Naive
+//! grid    <width>  <height>
+//! run     <ns per generation, comma-separated>  <rss kB>  <peak rss kB>  <heap bytes>  <peak heap bytes>  <allocations>
+//! end
+//! ```
+//!
+//! with one `run` line per repetition and `-` for heap figures that were not counted, or
+//! `skipped <reason>` instead of the `run` lines if the engine cannot run the job.
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wasm_game_of_life::bench::EngineResult;
+use wasm_game_of_life::memory::{HeapStats, MemoryUse};
+
+use crate::cli::Options;
+
+const HEADER: &str = "job-result 1";
+
+/// What became of a job
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Done(EngineResult),
+    Skipped(String), // The engine cannot run this rule, topology or size
+    TimedOut,
+    Failed(String),  // The child process crashed or reported nonsense
+}
+
+/// The report a child process prints for `outcome`, which is `Done` or `Skipped`
+pub fn encode(engine: &str, (width, height): (usize, usize), outcome: &Outcome) -> String {
+    let mut lines = vec![HEADER.to_string(), format!("engine\t{}", engine), format!("grid\t{}\t{}", width, height)];
+    match outcome {
+        Outcome::Done(result) => {
+            for (times, memory) in result.generation_ns.iter().zip(&result.memory) {
+                let times: Vec<String> = times.iter().map(u64::to_string).collect();
+                let heap = match memory.heap {
+                    Some(heap) => format!("{}\t{}\t{}", heap.allocated_bytes, heap.peak_bytes, heap.allocations),
+                    None => "-\t-\t-".to_string(),
+                };
+                lines.push(format!("run\t{}\t{}\t{}\t{}", times.join(","), memory.rss_kb, memory.peak_rss_kb, heap));
+            }
+        }
+        Outcome::Skipped(reason) => lines.push(format!("skipped\t{}", reason.replace(['\t', '\n'], " "))),
+        Outcome::TimedOut | Outcome::Failed(_) => panic!("Only finished jobs are reported"),
+    }
+    lines.push("end".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// Parses the report of a child process; lines before the header are ignored
+pub fn decode(output: &str) -> Result<Outcome, String> {
+    let mut lines = output.lines().skip_while(|line| *line != HEADER).skip(1);
+    let mut field = |name: &str| -> Result<String, String> {
+        match lines.next().and_then(|line| line.split_once('\t')) {
+            Some((key, value)) if key == name => Ok(value.to_string()),
+            _ => Err(format!("expected a '{}' line in the job report", name)),
+        }
+    };
+    let engine = field("engine")?;
+    let grid = field("grid")?;
+
+    let number = |value: &str| value.parse::<u64>().map_err(|_| format!("invalid number '{}' in the job report", value));
+    let (width, height) = match grid.split_once('\t') {
+        Some((width, height)) => (number(width)? as usize, number(height)? as usize),
+        None => return Err(format!("invalid grid '{}' in the job report", grid)),
+    };
+    let mut generation_ns = Vec::new();
+    let mut memory = Vec::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            ["end"] => {
+                if generation_ns.is_empty() {
+                    return Err("the job report has no runs".to_string());
+                }
+                return Ok(Outcome::Done(EngineResult { engine, width, height, generation_ns, memory }));
+            }
+            ["skipped", reason] => return Ok(Outcome::Skipped(reason.to_string())),
+            ["run", times, rss, peak_rss, heap @ ..] if heap.len() == 3 => {
+                let times = if times.is_empty() { Vec::new() } else { times.split(',').map(number).collect::<Result<_, _>>()? };
+                generation_ns.push(times);
+                let heap = match heap {
+                    ["-", "-", "-"] => None,
+                    [allocated, peak, allocations] => Some(HeapStats {
+                        allocated_bytes: number(allocated)?,
+                        peak_bytes: number(peak)?,
+                        allocations: number(allocations)?,
+                    }),
+                    _ => unreachable!(),
+                };
+                memory.push(MemoryUse { rss_kb: number(rss)?, peak_rss_kb: number(peak_rss)?, heap });
+            }
+            _ => return Err(format!("unexpected line '{}' in the job report", line)),
+        }
+    }
+    Err("the job report is incomplete".to_string())
+}
+
+/// Arguments that make a child process run `engine` on a `width x height` grid of the pattern in `file_path`
+fn job_args(options: &Options, file_path: &str, engine: &str, (width, height): (usize, usize)) -> Vec<String> {
+    let mut args = vec![
+        file_path.to_string(),
+        "--job".to_string(),
+        "--sizes".to_string(),
+        format!("{}x{}", width, height),
+        "--engines".to_string(),
+        engine.to_string(),
+        "--iterations".to_string(),
+        options.iterations.to_string(),
+        "--warmup".to_string(),
+        options.warmup.to_string(),
+        "--repetitions".to_string(),
+        options.repetitions.to_string(),
+    ];
+    if let Some(topology) = options.topology {
+        args.extend(["--topology".to_string(), topology.to_string()]);
+    }
+    args
+}
+
+/// Reads all of a child's output on another thread, so that a full pipe cannot stall the child
+fn read_all<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut output);
+        }
+        output
+    })
+}
+
+/// Runs one job in a child process running this same program, killing it after `options.timeout` seconds
+pub fn run_isolated(options: &Options, file_path: &str, engine: &str, grid: (usize, usize)) -> Outcome {
+    let program = match std::env::current_exe() {
+        Ok(program) => program,
+        Err(e) => return Outcome::Failed(format!("cannot find the benchmark program: {}", e)),
+    };
+    let mut child = match Command::new(program)
+        .args(job_args(options, file_path, engine, grid))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return Outcome::Failed(format!("cannot start a child process: {}", e)),
+    };
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+
+    let deadline = Instant::now() + Duration::from_secs(options.timeout);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Outcome::TimedOut;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => return Outcome::Failed(format!("cannot wait for the child process: {}", e)),
+        }
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Outcome::Failed(format!("child process exited with {}: {}", status, stderr.trim()));
+    }
+    decode(&stdout).unwrap_or_else(Outcome::Failed)
+}
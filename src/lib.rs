@@ -1,13 +1,26 @@
 extern crate cfg_if;
 extern crate wasm_bindgen;
-extern crate sysinfo; // Add sysinfo crate
 
 pub mod sparse_matrix;
-mod utils;
+pub mod optimized_alg;
+pub mod track_alive_cells;
+pub mod parallelize;
+pub mod hashed_parallel;
+pub mod bitwise;
+pub mod hashlife;
+pub mod traits;
+pub mod rules;
+pub mod topology;
+pub mod utils;
+pub mod verify;
+pub mod bench;
+pub mod memory;
 
 use cfg_if::cfg_if;
-use sysinfo::{System, SystemExt}; // Import sysinfo
 use wasm_bindgen::prelude::*;
+use traits::LifeUniverse;
+use rules::Rule;
+use topology::Topology;
 
 cfg_if! {
     if #[cfg(feature = "wee_alloc")] {
@@ -17,14 +30,6 @@ cfg_if! {
     }
 }
 
-// Function to print memory usage
-fn print_memory_usage(label: &str) {
-    let mut sys = System::new_all();
-    sys.refresh_memory();
-    let memory_used = sys.used_memory();
-    println!("{} - Memory Usage: {} MB", label, memory_used / 1024);
-}
-
 #[wasm_bindgen(start)]
 pub fn main() {
     console_error_panic_hook::set_once();
@@ -43,6 +48,8 @@ pub struct Universe {
     width: usize,
     height: usize,
     cells: Vec<Cell>, // Private field
+    rule: Rule,
+    topology: Topology,
 }
 
 impl Universe {
@@ -52,20 +59,39 @@ impl Universe {
 
     fn live_neighbor_count(&self, row: usize, column: usize) -> usize {
         let mut count = 0;
-        for delta_row in [self.height - 1, 0, 1].iter().cloned() {
-            for delta_col in [self.width - 1, 0, 1].iter().cloned() {
+        for delta_row in -1..=1 {
+            for delta_col in -1..=1 {
                 if delta_row == 0 && delta_col == 0 {
                     continue;
                 }
 
-                let neighbor_row = (row + delta_row) % self.height;
-                let neighbor_col = (column + delta_col) % self.width;
-                let idx = self.get_index(neighbor_row, neighbor_col);
-                count += self.cells[idx] as usize;
+                if let Some((neighbor_row, neighbor_col)) =
+                    self.topology.neighbour(self.width, self.height, row, column, delta_row, delta_col)
+                {
+                    let idx = self.get_index(neighbor_row, neighbor_col);
+                    count += self.cells[idx] as usize;
+                }
             }
         }
         count
     }
+
+    /// 3x3 neighbourhood of a cell as a lookup-table index, bit `3 * row + col`
+    /// set for each live cell (the cell itself is bit 4)
+    fn neighbourhood(&self, row: usize, column: usize) -> usize {
+        let mut index = 0;
+        for delta_row in -1..=1 {
+            for delta_col in -1..=1 {
+                if let Some((neighbor_row, neighbor_col)) =
+                    self.topology.neighbour(self.width, self.height, row, column, delta_row, delta_col)
+                {
+                    let idx = self.get_index(neighbor_row, neighbor_col);
+                    index |= (self.cells[idx] as usize) << (3 * (delta_row + 1) + delta_col + 1);
+                }
+            }
+        }
+        index
+    }
 }
 
 #[wasm_bindgen]
@@ -77,14 +103,18 @@ impl Universe{
             for col in 0..self.width {
                 let idx = self.get_index(row, col);
                 let cell = self.cells[idx];
-                let live_neighbors = self.live_neighbor_count(row, col);
-
-                let next_cell = match (cell, live_neighbors) {
-                    (Cell::Alive, x) if x < 2 => Cell::Dead,
-                    (Cell::Alive, 2) | (Cell::Alive, 3) => Cell::Alive,
-                    (Cell::Alive, x) if x > 3 => Cell::Dead,
-                    (Cell::Dead, 3) => Cell::Alive,
-                    (otherwise, _) => otherwise,
+
+                let alive = if self.rule.is_totalistic() {
+                    let live_neighbors = self.live_neighbor_count(row, col);
+                    self.rule.next_state(cell == Cell::Alive, live_neighbors)
+                } else {
+                    self.rule.next_state_neighbourhood(self.neighbourhood(row, col))
+                };
+
+                let next_cell = if alive {
+                    Cell::Alive
+                } else {
+                    Cell::Dead
                 };
 
                 next[idx] = next_cell;
@@ -96,11 +126,11 @@ impl Universe{
 
     pub fn new_with_cells(width: usize, height: usize, cells: Vec<Cell>) -> Universe {
         assert_eq!(cells.len(), width * height);
-        Universe { width, height, cells }
+        Universe { width, height, cells, rule: Rule::default(), topology: Topology::default() }
     }
 
     pub fn run_iterations(&mut self, iterations: usize) {
-        print_memory_usage("Before Running Iterations");
+        memory::print_memory_usage("Before Running Iterations");
 
         for _ in 0..iterations {
             self.tick();
@@ -141,4 +171,73 @@ impl fmt::Display for Universe {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl LifeUniverse for Universe {
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
+        let cells = flat_matrix
+            .iter()
+            .map(|&x| if x == 1 { Cell::Alive } else { Cell::Dead })
+            .collect();
+        Universe::new_with_cells(width, height, cells)
+    }
+
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn supports_rule(&self, rule: &Rule) -> bool {
+        // Cells are two-variant enums, so there is no room for dying states
+        !rule.is_generations()
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(self.supports_rule(&rule), "The naive engine does not support the rule {}", rule);
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn set_topology(&mut self, topology: Topology) {
+        assert!(self.supports_topology(topology), "The naive engine does not support the {} topology", topology);
+        topology.check_size(self.width, self.height);
+        self.topology = topology;
+    }
+
+    fn tick(&mut self) {
+        Universe::tick(self)
+    }
+
+    fn get_cell(&self, row: usize, col: usize) -> bool {
+        self.cells[self.get_index(row, col)] == Cell::Alive
+    }
+
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool) {
+        let idx = self.get_index(row, col);
+        self.cells[idx] = if alive { Cell::Alive } else { Cell::Dead };
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        Box::new(
+            self.cells
+                .iter()
+                .enumerate()
+                .filter(|&(_, &cell)| cell == Cell::Alive)
+                .map(move |(idx, _)| (idx / self.width, idx % self.width)),
+        )
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn render(&self) -> String {
+        Universe::render(self)
+    }
+}
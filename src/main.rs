@@ -1,241 +1,402 @@
 #![allow(unused_imports)]
-mod optimized_alg;
-mod track_alive_cells;
-mod parallelize;
-mod traits;  // This declares the traits module
-mod hashed_parallel;
-mod bitwise;
-
-use crate::traits::TickUniv;
-use hashed_parallel::Universe as HashParallelUniverse;
-
-mod hashlife;  // New Hashlife module
-
-use hashlife::Universe as HashlifeUniverse;  // New import for Hashlife
-use parallelize::Universe as ParallelUniverse;
-use optimized_alg::Universe as OptimizedUniverse;
-use wasm_game_of_life::{Universe as NaiveUniverse, Cell as NaiveCell};
+use wasm_game_of_life::traits::LifeUniverse;
+use wasm_game_of_life::rules::Rule;
+use wasm_game_of_life::topology::Topology;
+use wasm_game_of_life::hashed_parallel::Universe as HashParallelUniverse;
+use wasm_game_of_life::hashlife::Universe as HashlifeUniverse;
+use wasm_game_of_life::parallelize::Universe as ParallelUniverse;
+use wasm_game_of_life::optimized_alg::Universe as OptimizedUniverse;
+use wasm_game_of_life::Universe as NaiveUniverse;
 use wasm_game_of_life::sparse_matrix::Universe as SparseUniverse;
-use track_alive_cells::Universe as TrackAliveCellsUniverse;
-use bitwise::Universe as BWUniverse;
+use wasm_game_of_life::track_alive_cells::Universe as TrackAliveCellsUniverse;
+use wasm_game_of_life::bitwise::Universe as BWUniverse;
 
 use std::time::Instant;
+use chrono::{SecondsFormat, Utc};
 use rand::Rng;
-use sysinfo::{System, SystemExt};
+use sysinfo::{CpuExt, System, SystemExt};
 use std::env;
 use std::convert::TryInto;
 use std::fs::File;
 use std::io::{self, Write};
 use csv::Writer;
 use std::fs;
+use std::path::Path;
+use std::process;
+
+use wasm_game_of_life::utils::*;
+use wasm_game_of_life::verify::compare_engines;
+use wasm_game_of_life::bench::{size_label, time_generations, EngineResult};
+use wasm_game_of_life::memory::MemoryUse;
+
+mod cli;
+mod compare;
+mod job;
+mod report;
+use cli::{Command, CompareOptions, Options, OutputFormat, ReportOptions};
+use job::Outcome;
+
+// Counting every allocation slows the engines down a little, so it is opt-in
+#[cfg(feature = "count_allocations")]
+#[global_allocator]
+static ALLOC: wasm_game_of_life::memory::CountingAllocator = wasm_game_of_life::memory::CountingAllocator;
+
+/// Builds the engine called `name` (one of `cli::ENGINES`) from a flat matrix
+fn build_engine(name: &str, flat_matrix: Vec<u8>, width: usize, height: usize) -> Box<dyn LifeUniverse> {
+    match name {
+        "Naive" => Box::new(NaiveUniverse::from_matrix(width, height, flat_matrix)),
+        "Sparse" => Box::new(SparseUniverse::from_matrix(width, height, flat_matrix)),
+        "Optimized" => Box::new(OptimizedUniverse::from_matrix(width, height, flat_matrix)),
+        "TrackAliveCells" => Box::new(TrackAliveCellsUniverse::from_matrix(width, height, flat_matrix)),
+        "Parallel" => Box::new(ParallelUniverse::from_matrix(width, height, flat_matrix)),
+        "HashParallel" => Box::new(HashParallelUniverse::from_matrix(width, height, flat_matrix)),
+        "Bitwise" => Box::new(BWUniverse::from_matrix(width, height, flat_matrix)),
+        "Hashlife" => Box::new(HashlifeUniverse::from_matrix(width, height, flat_matrix)),
+        _ => panic!("Unknown engine {}", name),
+    }
+}
 
-mod utils;
-use utils::*;
-
-enum AnyUniverse {
-    Naive(NaiveUniverse),
-    Sparse(SparseUniverse),
-    Optimized(OptimizedUniverse),
-    TrackAliveCells(TrackAliveCellsUniverse),
-    Parallel(ParallelUniverse),
-    HashParallel(HashParallelUniverse),
-    Bitwise(BWUniverse),
-    Hashlife(HashlifeUniverse)
-}
-
-fn initialize_all(flat_matrix: Vec<u8>, width: usize, height: usize) -> (
-    NaiveUniverse,
-    SparseUniverse,
-    OptimizedUniverse,
-    TrackAliveCellsUniverse,
-    ParallelUniverse,
-    HashParallelUniverse,
-    BWUniverse,
-    HashlifeUniverse
-) {
-      // NAIVE
-    // Convert flat matrix to 2D representation
-    let initial_state = vec_to_matrix(&flat_matrix, width);
-    let initial_structcells: Vec<NaiveCell> = initial_state
-        .iter()
-        .flatten()
-        .map(|&x| if x == 1 { NaiveCell::Alive } else { NaiveCell::Dead })
-        .collect();
-    let naive_universe = NaiveUniverse::new_with_cells(width, height, initial_structcells);
-
-    // SPARSE
-    let sparse_universe = SparseUniverse::new_with_matrix(width, height, flat_matrix.clone());
-
-    // OPTIMIZED
-    let optimized_universe = OptimizedUniverse::new(width, height, flat_matrix.clone());
+/// Builds the engine called `name` on a grid with the given rule and topology, or says why it cannot run them
+fn prepare_engine(name: &str, flat_matrix: &[u8], width: usize, height: usize, rule: Rule, topology: Topology) -> Result<Box<dyn LifeUniverse>, String> {
+    let mut universe = build_engine(name, flat_matrix.to_vec(), width, height);
+    if !universe.supports_rule(&rule) {
+        Err(format!("rule {} is not supported", rule))
+    } else if !universe.supports_topology(topology) {
+        Err(format!("the {} topology is not supported", topology))
+    } else {
+        universe.set_rule(rule);
+        universe.set_topology(topology);
+        Ok(universe)
+    }
+}
 
-    // TRACK
-    let initial_trackparl_cells: Vec<(usize, usize)> = initial_state
+/// Builds the given engines from the same initial state on a grid with the given topology,
+/// skipping those that cannot evolve `rule` or that topology, paired with their names
+fn initialize_all(engines: &[&'static str], flat_matrix: &[u8], width: usize, height: usize, rule: Rule, topology: Topology) -> Vec<(&'static str, Box<dyn LifeUniverse>)> {
+    engines
         .iter()
-        .enumerate()
-        .flat_map(|(row, cols)| {
-            cols.iter().enumerate().filter_map(move |(col, &value)| {
-                if value == 1 {
-                    Some((row, col))
-                } else {
-                    None
-                }
-            })
+        .filter_map(|&name| match prepare_engine(name, flat_matrix, width, height, rule, topology) {
+            Ok(universe) => Some((name, universe)),
+            Err(reason) => {
+                println!("Skipping {}: {}", name, reason);
+                None
+            }
         })
-        .collect();
+        .collect()
+}
 
-    let track_alive_cells_universe = TrackAliveCellsUniverse::new(
-        width,
-        height,
-        initial_trackparl_cells.clone(), // Clone here to preserve for parallel version
-    );
+/// Times one engine: `warmup` discarded runs, then `repetitions` measured ones, each of
+/// `iterations` generations from a fresh copy of the initial state
+fn gather_iteration_info(
+    options: &Options,
+    name: &str,
+    flat_matrix: &[u8],
+    (width, height): (usize, usize),
+    rule: Rule,
+    topology: Topology,
+) -> Result<(Vec<Vec<u64>>, Vec<MemoryUse>), String> {
+    for _ in 0..options.warmup {
+        let mut universe = prepare_engine(name, flat_matrix, width, height, rule, topology)?;
+        time_generations(universe.as_mut(), options.iterations);
+    }
 
-    // PARALLEL
-    let parallel_universe = ParallelUniverse::new(width, height, initial_trackparl_cells.clone());
+    let mut generation_ns = Vec::with_capacity(options.repetitions);
+    let mut memory = Vec::with_capacity(options.repetitions);
+    for _ in 0..options.repetitions {
+        // Building the engine counts towards its memory use
+        MemoryUse::start();
+        let mut universe = prepare_engine(name, flat_matrix, width, height, rule, topology)?;
+        generation_ns.push(time_generations(universe.as_mut(), options.iterations));
+        memory.push(MemoryUse::now());
+    }
 
-    //PARALLEL WITH HASHING
-    let hashed_parallel_universe = HashParallelUniverse::new_with_matrix(width, height, flat_matrix.clone());
+    Ok((generation_ns, memory))
+}
 
-    // BITWISE 
-    let bitwise_universe = BWUniverse::new(width, height, flat_matrix.clone());
+/// A pattern file and how the engines run it
+struct PatternRun {
+    file_name: String,
+    pattern: Pattern,
+    rule: Rule,
+    topology: Topology,
+    label: String,
+}
 
-    // HASHLIFE
-    let hashlife_universe = HashlifeUniverse::new_with_matrix(width, height, flat_matrix.clone());
+/// Reads a pattern file along with the rule and grid it declares
+fn load_pattern(options: &Options, file_path: &str) -> Result<PatternRun, String> {
+    let file_name = Path::new(file_path).file_name().and_then(|name| name.to_str()).unwrap_or(file_path).to_string();
+    // Pattern, rule and bounded grid declared in the file
+    let pattern = read_pattern(file_path).map_err(|e| format!("Cannot read {}: {}", file_path, e))?;
+    let rule = pattern.metadata.rule;
+    // By default the edges are dead so that escaping gliders do not wrap around and collide with the pattern
+    let topology = options.topology.or(pattern.metadata.topology).unwrap_or(Topology::Plane);
+    // Results are labelled with the pattern's name, and its author when known
+    let label = match &pattern.metadata.author {
+        Some(author) => format!("{} ({})", pattern.metadata.label(&file_name), author),
+        None => pattern.metadata.label(&file_name),
+    };
+    Ok(PatternRun { file_name, pattern, rule, topology, label })
+}
 
-    (naive_universe, sparse_universe, optimized_universe, track_alive_cells_universe, 
-        parallel_universe, hashed_parallel_universe, bitwise_universe, hashlife_universe)
+/// Times one engine in this process on a grid already holding the pattern
+fn measure(options: &Options, run: &PatternRun, name: &str, flat_matrix: &[u8], (width, height): (usize, usize)) -> Outcome {
+    match gather_iteration_info(options, name, flat_matrix, (width, height), run.rule, run.topology) {
+        Ok((generation_ns, memory)) => {
+            Outcome::Done(EngineResult { engine: name.to_string(), width, height, generation_ns, memory })
+        }
+        Err(reason) => Outcome::Skipped(reason),
+    }
 }
 
-// Advances one time step for any possible impl
-fn global_ticker(universe:&mut AnyUniverse){
+/// Model name of the CPU, "unknown" if the system does not say
+fn cpu_model() -> String {
+    let mut system = System::new();
+    system.refresh_cpu();
+    let model = system.cpus().first().map_or("", |cpu| cpu.brand().trim());
+    if model.is_empty() { "unknown".to_string() } else { model.to_string() }
+}
 
-    // Match on the enum and call the corresponding tick() method
-    match universe {
-        AnyUniverse::Naive(u) => u.tick(),
-        AnyUniverse::Sparse(u) => u.tick(),
-        AnyUniverse::Optimized(u) => u.tick(),
-        AnyUniverse::TrackAliveCells(u) => u.tick(),
-        AnyUniverse::Parallel(u) => u.tick(),
-        AnyUniverse::HashParallel(u) => u.tick(),
-        AnyUniverse::Bitwise(u) => u.tick(),
-        AnyUniverse::Hashlife(u) => u.tick(),
+/// Commit checked out in the current directory, marked `-dirty` when tracked files were changed
+fn git_revision() -> String {
+    let git = |args: &[&str]| {
+        let output = process::Command::new("git").args(args).output().ok().filter(|output| output.status.success())?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    match git(&["rev-parse", "--short=12", "HEAD"]) {
+        Some(revision) if git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|changes| !changes.is_empty()) => {
+            format!("{}-dirty", revision)
+        }
+        Some(revision) => revision,
+        None => "unknown".to_string(),
     }
 }
 
-fn gather_iteration_info(universe: &mut AnyUniverse, iterations: usize) -> (u128, Vec<u128>, Vec<u64>) {
-    
-    let mut iteration_times = Vec::new();
-    let mut memory_use = Vec::new();
-    memory_use.push(get_memory_usage()/1024);
+/// The current time in RFC 3339 format, in UTC
+fn utc_timestamp() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
 
-    /* let global_start = Instant::now(); 
+/// Runs every engine of `options` on one pattern file and writes its results file
+fn run_pattern(options: &Options, file_path: &str) -> Result<(), String> {
+    let run = load_pattern(options, file_path)?;
+    let (label, topology) = (&run.label, run.topology);
+    let info = RunInfo {
+        pattern: label.clone(),
+        rule: run.rule.to_string(),
+        topology: topology.to_string(),
+        iterations: options.iterations,
+        warmup: options.warmup,
+        repetitions: options.repetitions,
+        threads: rayon::current_num_threads(),
+        cpu_model: cpu_model(),
+        git_revision: git_revision(),
+        timestamp: utc_timestamp(),
+    };
 
-    match universe {
-        AnyUniverse::Naive(u) => u.run_iterations(iterations),
-        AnyUniverse::Sparse(u) => u.run_iterations(iterations),
-        AnyUniverse::Optimized(u) => u.run_iterations(iterations),
-        AnyUniverse::TrackAliveCells(u) => u.run_iterations(iterations),
-        AnyUniverse::Parallel(u) => u.run_iterations(iterations),
-        AnyUniverse::HashParallel(u) => u.run_iterations(iterations),
-        AnyUniverse::Bitwise(u) => u.run_iterations(iterations),
-    }
-    let global_time = global_start.elapsed().as_millis(); // Total elapsed time
- */
-    let global_start = Instant::now();
-    let mut iter_start = Instant::now();
-
-    // measuring every 10 iterations
-    for i in 0..iterations {
-        if i % 10 == 0 {
-            // Start the clock
-            iter_start = Instant::now();
-            global_ticker(universe);
-            //memory_use.push(get_memory_usage()/1024);
-
-        } else if i % 10 == 9 {
-
-            global_ticker(universe);
-            // Record time per 10 interations
-            let iter_time = iter_start.elapsed().as_millis();
-            iteration_times.push(iter_time);
-            
-        } else {
-            global_ticker(universe);
+    let mut all_results = Vec::new();
+    let mut failures = 0;
+    for (width, height) in options.sizes.grids() {
+        if !topology.supports_size(width, height) {
+            println!("Skipping {}x{}: a {} must be square", width, height, topology);
+            continue;
+        }
+        let size = size_label(width, height);
+        // Place the pattern in the middle of the universe
+        let flat_matrix = match run.pattern.place((width, height), Placement::Centered) {
+            Ok(flat_matrix) => flat_matrix,
+            Err(e) => {
+                println!("Skipping {}: {}", size, e);
+                continue;
+            }
+        };
+
+        for &name in &options.engines {
+            let outcome = if options.isolate {
+                job::run_isolated(options, file_path, name, (width, height))
+            } else {
+                measure(options, &run, name, &flat_matrix, (width, height))
+            };
+            let result = match outcome {
+                Outcome::Done(result) => result,
+                Outcome::Skipped(reason) => {
+                    println!("Skipping {}: {}", name, reason);
+                    continue;
+                }
+                Outcome::TimedOut => {
+                    println!("Timed out: {} {} {} after {} s", label, size, name, options.timeout);
+                    continue;
+                }
+                Outcome::Failed(e) => {
+                    eprintln!("Failed: {} {} {}: {}", label, size, name, e);
+                    failures += 1;
+                    continue;
+                }
+            };
+
+            // Print results
+            let summary = result.summary();
+            let peak = result.peak_memory();
+            println!(
+                "Done: {} {} {}: mean {:.3} ms, median {:.3} ms, stddev {:.3} ms over {} runs, peak RSS {} kB{}",
+                label,
+                size,
+                name,
+                summary.mean / 1e6,
+                summary.median / 1e6,
+                summary.stddev / 1e6,
+                summary.samples,
+                peak.peak_rss_kb,
+                peak.heap.map_or(String::new(), |heap| format!(", peak heap {} bytes", heap.peak_bytes))
+            );
+            all_results.push(result);
         }
     }
-    let global_time = global_start.elapsed().as_millis(); // Total elapsed time
-    memory_use.push(get_memory_usage()/1024);
-    
-    (global_time, iteration_times, memory_use)
+
+    let output_path = options.output_path(&run.file_name);
+    let written = match options.format {
+        OutputFormat::Csv => write_results_to_csv(&all_results, &output_path, &info),
+        OutputFormat::JsonLines => write_results_to_json(&all_results, &output_path, &info),
+    };
+    written.map_err(|e| format!("Error writing {}: {}", output_path, e))?;
+    println!("Results written to {}", output_path);
+
+    if failures > 0 {
+        return Err(format!("{} of the jobs on {} failed", failures, file_path));
+    }
+    Ok(())
 }
 
-fn main() {
-    // File name of the grid
-    let file_name = "dense_init.rle";
-    let file_path = format!("./grids/{}", file_name);
-    // Number of iterations:
-    let iterations: usize = 1000;
-
-    // ==== looping over grid sizes === 
-    let scales = vec![3];
-    // let scales = [1];
-    let mut all_results = Vec::new();
+/// Runs the single job of a child process started by `job::run_isolated` and reports it on stdout
+fn run_job(options: &Options) -> Result<(), String> {
+    let run = load_pattern(options, &options.patterns[0])?;
+    let (width, height) = options.sizes.grids()[0];
+    let name = options.engines[0];
 
-    for &scale in &scales {
-        // Size of the universe:
-        let width = usize::pow(2, 6 + scale);
-        // Read RLE file and initialize the flat matrix
-        let flat_matrix: Vec<u8> = init_from_file(&file_path, width);
-
-        // --- Initialization ---
-        let (naive_universe,  sparse_universe,  optimized_universe, track_alive_cells_universe, parallel_universe, 
-            hashed_parallel_universe, bitwise_universe, hashlife_universe) = initialize_all(flat_matrix, width, width);
-        let mut initial_universes: Vec<AnyUniverse> = vec![
-            AnyUniverse::Naive(naive_universe),
-            AnyUniverse::Sparse(sparse_universe),
-            AnyUniverse::Optimized(optimized_universe),
-            AnyUniverse::TrackAliveCells(track_alive_cells_universe),
-            AnyUniverse::Parallel(parallel_universe),
-            AnyUniverse::HashParallel(hashed_parallel_universe),
-            AnyUniverse::Bitwise(bitwise_universe),
-            AnyUniverse::Hashlife(hashlife_universe)
-        ];
-        let universe_names = [
-            "Naive", 
-            "Sparse", 
-            "Optimized", 
-            "TrackAliveCells", 
-            "Parallel", 
-            "HashParallel",
-            "Bitwise",
-            "Hashlife"
-        ];
-
-        // --- Result Printing ---
-        let mut version_results = Vec::new();
-
-        for (i, univ) in initial_universes.iter_mut().enumerate() {
-            let (global_time, iteration_times, memory_use) = gather_iteration_info(univ, iterations);
-            let name = universe_names[i];  // Get the name based on index
-
-            // Add the result to the results vector
-            version_results.push((width, name.to_string(), global_time, iteration_times.clone(), memory_use.clone()));
+    let outcome = if run.topology.supports_size(width, height) {
+        match run.pattern.place((width, height), Placement::Centered) {
+            Ok(flat_matrix) => measure(options, &run, name, &flat_matrix, (width, height)),
+            Err(e) => Outcome::Skipped(e.to_string()),
+        }
+    } else {
+        Outcome::Skipped(format!("a {} must be square", run.topology))
+    };
+    print!("{}", job::encode(name, (width, height), &outcome));
+    Ok(())
+}
 
-            // Print results
-            println!("Done: {} {}: ", width, name);
-            //println!();  // Empty line after each result
+/// Runs every engine of `options` on one pattern file side by side, checking that their
+/// live cells agree. Returns whether they all did.
+fn verify_pattern(options: &Options, file_path: &str) -> Result<bool, String> {
+    let pattern = read_pattern(file_path).map_err(|e| format!("Cannot read {}: {}", file_path, e))?;
+    let rule = pattern.metadata.rule;
+    let topology = options.topology.or(pattern.metadata.topology).unwrap_or(Topology::Plane);
+
+    let mut agreed = true;
+    for (width, height) in options.sizes.grids() {
+        if !topology.supports_size(width, height) {
+            println!("Skipping {}x{}: a {} must be square", width, height, topology);
+            continue;
+        }
+        let flat_matrix = match pattern.place((width, height), Placement::Centered) {
+            Ok(flat_matrix) => flat_matrix,
+            Err(e) => {
+                println!("Skipping {}x{}: {}", width, height, e);
+                continue;
+            }
+        };
+        let mut universes = initialize_all(&options.engines, &flat_matrix, width, height, rule, topology);
+
+        match compare_engines(&mut universes, options.iterations, options.check_every) {
+            Ok(()) => println!("OK: {} {}x{}: all engines agree for {} generations", file_path, width, height, options.iterations),
+            Err(divergence) => {
+                println!("DIVERGED: {} {}x{}: {}", file_path, width, height, divergence);
+                agreed = false;
+            }
         }
-        all_results.push(version_results);
-        
     }
+    Ok(agreed)
+}
 
-    let output_file_name = format!("{}_{}_{:?}_results.csv", file_name, iterations, scales.last().unwrap());  // Use the existing `file_name` variable
-
-    if let Err(e) = write_results_to_csv(&all_results, &output_file_name, iterations, file_name) {
-        eprintln!("Error writing to CSV file: {}", e);
+/// Draws the charts of every results file of `options`
+fn run_report(options: &ReportOptions) -> Result<(), String> {
+    for file_path in &options.results {
+        let results = read_results(file_path).map_err(|e| format!("Cannot read {}: {}", file_path, e))?;
+        let stem = Path::new(file_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("results");
+        let charts = report::render_charts(&results, stem, &options.out_dir, options.format)
+            .map_err(|e| format!("Cannot draw the charts of {}: {}", file_path, e))?;
+        for chart in charts {
+            println!("Chart written to {}", chart.display());
+        }
     }
+    Ok(())
 }
 
+/// Compares the results files of `options`; returns whether any engine regressed
+fn run_compare(options: &CompareOptions) -> Result<bool, String> {
+    let read = |file_path: &str| read_results(file_path).map_err(|e| format!("Cannot read {}: {}", file_path, e));
+    let (baseline, candidate) = (read(&options.baseline)?, read(&options.candidate)?);
+    Ok(compare::compare_results(&baseline, &candidate, options.threshold)? > 0)
+}
 
+fn main() {
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Report(report_options)) => {
+            if let Err(e) = run_report(&report_options) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::Compare(compare_options)) => {
+            match run_compare(&compare_options) {
+                Ok(false) => return,
+                Ok(true) => process::exit(1),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Ok(Command::ReportHelp) => {
+            print!("{}", cli::REPORT_USAGE);
+            return;
+        }
+        Ok(Command::CompareHelp) => {
+            print!("{}", cli::COMPARE_USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\nRun with --help for usage.", e);
+            process::exit(2);
+        }
+    };
 
+    if options.job {
+        if let Err(e) = run_job(&options) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
 
+    let mut failed = false;
+    for file_path in &options.patterns {
+        let result = if options.verify {
+            verify_pattern(&options, file_path).map(|agreed| failed |= !agreed)
+        } else {
+            run_pattern(&options, file_path)
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,153 @@
+//! Memory use of this process: resident set size from `/proc/self`, and heap use as seen by
+//! `CountingAllocator` when a binary installs it as its global allocator
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Resident set size of the process, in kB
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcessMemory {
+    pub rss_kb: u64,      // Current resident set size (VmRSS)
+    pub peak_rss_kb: u64, // Highest resident set size since start or since `reset_peak_rss` (VmHWM)
+}
+
+/// Reads the resident set size from `/proc/self/status`; `None` where there is no procfs
+pub fn process_memory() -> Option<ProcessMemory> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let line = status.lines().find(|line| line.starts_with(name))?;
+        line[name.len()..].trim().trim_end_matches("kB").trim().parse().ok()
+    };
+    Some(ProcessMemory { rss_kb: field("VmRSS:")?, peak_rss_kb: field("VmHWM:")? })
+}
+
+/// Prints the current and peak resident set size under `label`, where procfs can tell
+pub fn print_memory_usage(label: &str) {
+    if let Some(process) = process_memory() {
+        println!("{} - Memory Usage: {} MB (peak {} MB)", label, process.rss_kb / 1024, process.peak_rss_kb / 1024);
+    }
+}
+
+/// Resets the peak resident set size to the current one. Returns whether the kernel allowed it;
+/// if not, the peak keeps covering the whole life of the process.
+pub fn reset_peak_rss() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static LIVE: AtomicU64 = AtomicU64::new(0);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+/// The system allocator, counting what goes through it. Install it with
+/// `#[global_allocator] static ALLOC: CountingAllocator = CountingAllocator;`
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn record_alloc(size: usize) {
+        COUNTING.store(true, Ordering::Relaxed);
+        ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let live = LIVE.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+        PEAK.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn record_dealloc(size: usize) {
+        LIVE.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::record_dealloc(layout.size());
+            Self::record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Heap use since the last `reset_heap_stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub allocated_bytes: u64, // Total bytes allocated, including those freed since
+    pub peak_bytes: u64,      // Most bytes live at once
+    pub allocations: u64,     // Number of allocations, reallocations included
+}
+
+/// Heap use counted by `CountingAllocator`; `None` if it is not the global allocator
+pub fn heap_stats() -> Option<HeapStats> {
+    if !COUNTING.load(Ordering::Relaxed) {
+        return None;
+    }
+    Some(HeapStats {
+        allocated_bytes: ALLOCATED.load(Ordering::Relaxed),
+        peak_bytes: PEAK.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+    })
+}
+
+/// Starts counting afresh: the peak restarts from the bytes live now
+pub fn reset_heap_stats() {
+    ALLOCATED.store(0, Ordering::Relaxed);
+    ALLOCATIONS.store(0, Ordering::Relaxed);
+    PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Memory use of one benchmark run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUse {
+    pub rss_kb: u64,              // Resident set size at the end of the run
+    pub peak_rss_kb: u64,         // Highest resident set size during the run
+    pub heap: Option<HeapStats>,  // Heap use during the run, with `CountingAllocator` only
+}
+
+impl MemoryUse {
+    /// Resets the peaks, to be called before a run measured with `MemoryUse::now`
+    pub fn start() {
+        reset_peak_rss();
+        reset_heap_stats();
+    }
+
+    /// Memory use since the last `MemoryUse::start`
+    pub fn now() -> MemoryUse {
+        let process = process_memory().unwrap_or_default();
+        MemoryUse { rss_kb: process.rss_kb, peak_rss_kb: process.peak_rss_kb, heap: heap_stats() }
+    }
+
+    /// The larger of each figure of `self` and `other`
+    pub fn max(self, other: MemoryUse) -> MemoryUse {
+        let heap = match (self.heap, other.heap) {
+            (Some(a), Some(b)) => Some(HeapStats {
+                allocated_bytes: a.allocated_bytes.max(b.allocated_bytes),
+                peak_bytes: a.peak_bytes.max(b.peak_bytes),
+                allocations: a.allocations.max(b.allocations),
+            }),
+            (a, b) => a.or(b),
+        };
+        MemoryUse { rss_kb: self.rss_kb.max(other.rss_kb), peak_rss_kb: self.peak_rss_kb.max(other.peak_rss_kb), heap }
+    }
+}
@@ -1,4 +1,7 @@
 #![allow(dead_code)]
+use crate::traits::LifeUniverse;
+use crate::rules::Rule;
+use crate::topology::Topology;
 
 //this is still a sequential algorithm but it has some optimizations for cache eficiency
 pub struct Universe {
@@ -6,6 +9,8 @@ pub struct Universe {
     height: usize,          // The height of the grid (number of rows)
     current: Vec<u8>,       // Flat representation of the grid's current state; 0 for dead, 1 for alive
     next: Vec<u8>,          // Flat representation of the grid's next state
+    rule: Rule,             // Birth/survival rule applied at each tick
+    topology: Topology,     // How neighbours are found across the grid edges
 }
 
 impl Universe {
@@ -18,6 +23,8 @@ impl Universe {
             height,
             current: initial_state,           // Set the current grid to the provided initial state.
             next: vec![0; width * height],    // Initialize the next grid with all cells dead (0).
+            rule: Rule::default(),            // Conway's B3/S23 unless set otherwise.
+            topology: Topology::default(),    // Toroidal wrapping unless set otherwise.
         }
     }
 
@@ -35,13 +42,14 @@ impl Universe {
         for row in 0..self.height {
             for col in 0..self.width {
                 let idx = self.get_index(row, col); // Calculate the 1D index for the cell.
-                let live_neighbors = self.count_live_neighbors(row, col, &deltas); // Count the live neighbors.
 
-                // Apply the Game of Life rules to determine the next state of the cell.
-                self.next[idx] = match (self.current[idx], live_neighbors) {
-                    (1, 2) | (1, 3) => 1, // A live cell with 2 or 3 live neighbors stays alive.
-                    (0, 3) => 1,          // A dead cell with exactly 3 live neighbors becomes alive.
-                    _ => 0,               // Otherwise, the cell dies or remains dead.
+                // Apply the rule to determine the next state of the cell (dying states decay).
+                self.next[idx] = if self.rule.is_totalistic() {
+                    let live_neighbors = self.count_live_neighbors(row, col, &deltas); // Count the live neighbors.
+                    self.rule.next_cell_state(self.current[idx], live_neighbors as usize)
+                } else {
+                    // Non-totalistic rules need the exact shape of the neighbourhood.
+                    self.rule.next_cell_state_neighbourhood(self.current[idx], self.neighbourhood(row, col))
                 };
             }
         }
@@ -56,20 +64,46 @@ impl Universe {
 
         // Iterate through all neighbor positions defined by `deltas`.
         for &(dr, dc) in deltas {
-            // Calculate the wrapped row and column positions (toroidal wrapping).
-            let neighbor_row = (row as isize + dr as isize + self.height as isize) % self.height as isize;
-            let neighbor_col = (col as isize + dc as isize + self.width as isize) % self.width as isize;
+            // Find the neighbor across the edges according to the topology, skipping dead borders.
+            let Some((neighbor_row, neighbor_col)) =
+                self.topology.neighbour(self.width, self.height, row, col, dr as isize, dc as isize)
+            else {
+                continue;
+            };
 
             // Convert the wrapped row and column back to 1D index.
-            let idx = self.get_index(neighbor_row as usize, neighbor_col as usize);
+            let idx = self.get_index(neighbor_row, neighbor_col);
 
-            // Add the value of the neighbor cell to the count (0 or 1).
-            count += self.current[idx];
+            // Count the neighbor only if it is alive (dying cells under Generations rules do not count).
+            count += (self.current[idx] == 1) as u8;
         }
 
         count
     }
 
+    /// Packs the 3x3 block around `(row, col)` into a lookup-table index, bit `3 * row + col`
+    /// of the block being set for each live cell (the cell itself is bit 4).
+    fn neighbourhood(&self, row: usize, col: usize) -> usize {
+        let mut index = 0;
+
+        for dr in -1..=1 {
+            for dc in -1..=1 {
+                let Some((neighbor_row, neighbor_col)) =
+                    self.topology.neighbour(self.width, self.height, row, col, dr, dc)
+                else {
+                    continue;
+                };
+                let idx = self.get_index(neighbor_row, neighbor_col);
+
+                // Position of this cell within the 3x3 block.
+                let bit = 3 * (dr + 1) + (dc + 1);
+                index |= ((self.current[idx] == 1) as usize) << bit;
+            }
+        }
+
+        index
+    }
+
     /// Converts a 2D coordinate `(row, col)` into a 1D index for the flat grid representation.
     fn get_index(&self, row: usize, col: usize) -> usize {
         row * self.width + col // Calculate the 1D index using row-major order.
@@ -100,3 +134,74 @@ impl Universe {
     }
 }
 
+impl LifeUniverse for Universe {
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Self {
+        Universe::new(width, height, flat_matrix)
+    }
+
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn supports_rule(&self, _rule: &Rule) -> bool {
+        true
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(self.supports_rule(&rule), "The optimized engine does not support the rule {}", rule);
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn set_topology(&mut self, topology: Topology) {
+        assert!(self.supports_topology(topology), "The optimized engine does not support the {} topology", topology);
+        topology.check_size(self.width, self.height);
+        self.topology = topology;
+    }
+
+    fn tick(&mut self) {
+        Universe::tick(self)
+    }
+
+    fn get_cell(&self, row: usize, col: usize) -> bool {
+        self.current[self.get_index(row, col)] == 1
+    }
+
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool) {
+        self.set_state(row, col, alive as u8);
+    }
+
+    fn get_state(&self, row: usize, col: usize) -> u8 {
+        self.current[self.get_index(row, col)]
+    }
+
+    fn set_state(&mut self, row: usize, col: usize, state: u8) {
+        let idx = self.get_index(row, col);
+        self.current[idx] = state;
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        Box::new(
+            self.current
+                .iter()
+                .enumerate()
+                .filter(|&(_, &cell)| cell == 1)
+                .map(move |(idx, _)| (idx / self.width, idx % self.width)),
+        )
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn render(&self) -> String {
+        Universe::render(self)
+    }
+}
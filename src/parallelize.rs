@@ -1,5 +1,8 @@
 #![allow(dead_code)]
 use rayon::prelude::*;
+use crate::traits::LifeUniverse;
+use crate::rules::Rule;
+use crate::topology::Topology;
 
 pub struct Universe {
     width: usize,
@@ -7,36 +10,57 @@ pub struct Universe {
     current: Vec<u8>, // Flat representation of the grid
     next: Vec<u8>,    // Auxiliary grid for the next state
     active: Vec<bool>, // Flat representation of active cells
+    rule: Rule,
+    topology: Topology,
 }
 
 impl Universe {
     /// Creates a new `Universe` with the specified dimensions and initial live cells.
     pub fn new(width: usize, height: usize, initial_live_cells: Vec<(usize, usize)>) -> Self {
         let mut current = vec![0; width * height];
-        let mut active = vec![false; width * height];
 
         for &(row, col) in &initial_live_cells {
             let idx = row * width + col;
             current[idx] = 1; // Mark the cell as alive
-            active[idx] = true; // Mark the cell as active
         }
 
-        Self {
+        let mut universe = Self {
             width,
             height,
             current,
             next: vec![0; width * height],
-            active,
+            active: Vec::new(),
+            rule: Rule::default(),
+            topology: Topology::default(),
+        };
+        universe.activate_all();
+        universe
+    }
+
+    /// Marks every cell that can change in the next tick as active: all cells that are
+    /// not dead and their neighbours, since without B0 no other cell can be born
+    fn activate_all(&mut self) {
+        self.active = vec![false; self.width * self.height];
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = self.get_index(row, col);
+                if self.current[idx] == 0 {
+                    continue;
+                }
+                self.active[idx] = true;
+                for &(dr, dc) in self.neighbor_deltas().iter() {
+                    if let Some(neighbor_idx) = self.neighbor_index(row, col, dr, dc) {
+                        self.active[neighbor_idx] = true;
+                    }
+                }
+            }
         }
     }
 
     /// Advances the game by one tick (parallelized).
     pub fn tick(&mut self) {
-        let mut new_next = vec![0; self.width * self.height];
-        let mut new_active = vec![false; self.width * self.height];
-
-        // Process rows in parallel
-        let updates: Vec<(usize, u8, bool)> = (0..self.height)
+        // Process rows in parallel, collecting the cells that change
+        let updates: Vec<(usize, u8)> = (0..self.height)
             .into_par_iter()
             .flat_map(|row| {
                 let mut local_updates = Vec::new();
@@ -51,38 +75,35 @@ impl Universe {
 
                     let live_neighbors = self.count_live_neighbors(row, col);
 
-                    // Apply Game of Life rules
-                    let next_state = match (self.current[idx], live_neighbors) {
-                        (1, 2) | (1, 3) => 1, // Alive cell survives
-                        (0, 3) => 1,          // Dead cell becomes alive
-                        _ => 0,               // Otherwise, the cell dies
-                    };
+                    // Apply the birth/survival rule
+                    let next_state = self.rule.next_cell_state(self.current[idx], live_neighbors as usize);
 
                     // Check if the cell changed
                     if next_state != self.current[idx] {
-                        local_updates.push((idx, next_state, true));
-
-                        // Mark neighbors as active
-                        for &(dr, dc) in self.neighbor_deltas().iter() {
-                            let neighbor_row = (row as isize + dr + self.height as isize) % self.height as isize;
-                            let neighbor_col = (col as isize + dc + self.width as isize) % self.width as isize;
-                            let neighbor_idx = self.get_index(neighbor_row as usize, neighbor_col as usize);
-                            local_updates.push((neighbor_idx, self.next[neighbor_idx], true));
-                        }
+                        local_updates.push((idx, next_state));
                     }
                 }
                 local_updates
             })
             .collect();
 
-        // Merge updates sequentially to avoid data race
-        for (idx, value, active) in updates {
-            new_next[idx] = value;
-            new_active[idx] = active;
+        // Unchanged cells keep their state. Only changed cells and their neighbours can change
+        // in the next tick, since nothing else sees a different neighbourhood.
+        self.next.copy_from_slice(&self.current);
+        let mut new_active = vec![false; self.width * self.height];
+        for &(idx, state) in &updates {
+            self.next[idx] = state;
+            new_active[idx] = true;
+            let (row, col) = (idx / self.width, idx % self.width);
+            for &(dr, dc) in self.neighbor_deltas().iter() {
+                if let Some(neighbor_idx) = self.neighbor_index(row, col, dr, dc) {
+                    new_active[neighbor_idx] = true;
+                }
+            }
         }
 
         // Swap grids and update active cells
-        self.current = new_next;
+        std::mem::swap(&mut self.current, &mut self.next);
         self.active = new_active;
     }
 
@@ -90,11 +111,9 @@ impl Universe {
     fn count_live_neighbors(&self, row: usize, col: usize) -> u8 {
         self.neighbor_deltas()
             .iter()
-            .fold(0, |count, &(dr, dc)| {
-                let neighbor_row = (row as isize + dr + self.height as isize) % self.height as isize;
-                let neighbor_col = (col as isize + dc + self.width as isize) % self.width as isize;
-                let neighbor_idx = self.get_index(neighbor_row as usize, neighbor_col as usize);
-                count + self.current[neighbor_idx]
+            .fold(0, |count, &(dr, dc)| match self.neighbor_index(row, col, dr, dc) {
+                Some(neighbor_idx) => count + (self.current[neighbor_idx] == 1) as u8, // Dying cells do not count
+                None => count, // Beyond a dead edge
             })
     }
 
@@ -107,6 +126,14 @@ impl Universe {
         ]
     }
 
+    /// Flat index of the neighbour at offset `(dr, dc)`, following the topology across the edges.
+    #[inline]
+    fn neighbor_index(&self, row: usize, col: usize, dr: isize, dc: isize) -> Option<usize> {
+        self.topology
+            .neighbour(self.width, self.height, row, col, dr, dc)
+            .map(|(neighbor_row, neighbor_col)| self.get_index(neighbor_row, neighbor_col))
+    }
+
     /// Converts a 2D coordinate `(row, col)` into a 1D index for the flat grid.
     #[inline]
     fn get_index(&self, row: usize, col: usize) -> usize {
@@ -135,4 +162,106 @@ impl Universe {
     }
 }
 
+impl LifeUniverse for Universe {
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Self {
+        let live_cells = flat_matrix
+            .iter()
+            .enumerate()
+            .filter(|&(_, &value)| value == 1)
+            .map(|(idx, _)| (idx / width, idx % width))
+            .collect();
+        let mut universe = Universe::new(width, height, live_cells);
+
+        // Dying cells of Generations rules keep their state and stay active
+        for (idx, &value) in flat_matrix.iter().enumerate() {
+            if value > 1 {
+                universe.current[idx] = value;
+            }
+        }
+        universe.activate_all();
+        universe
+    }
+
+    fn from_live_cells(width: usize, height: usize, live_cells: Vec<(usize, usize)>) -> Self {
+        Universe::new(width, height, live_cells)
+    }
+
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn supports_rule(&self, rule: &Rule) -> bool {
+        // Only neighbour counts are available, but cells can hold dying states
+        rule.is_totalistic()
+    }
 
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(self.supports_rule(&rule), "The parallel engine does not support the rule {}", rule);
+        self.rule = rule;
+        // Cells that were stable under the old rule may change under the new one
+        self.activate_all();
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn set_topology(&mut self, topology: Topology) {
+        assert!(self.supports_topology(topology), "The parallel engine does not support the {} topology", topology);
+        topology.check_size(self.width, self.height);
+        self.topology = topology;
+        // Cells across the edges may have become neighbours
+        self.activate_all();
+    }
+
+    fn tick(&mut self) {
+        Universe::tick(self)
+    }
+
+    fn get_cell(&self, row: usize, col: usize) -> bool {
+        self.current[self.get_index(row, col)] == 1
+    }
+
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool) {
+        self.set_state(row, col, alive as u8);
+    }
+
+    fn get_state(&self, row: usize, col: usize) -> u8 {
+        self.current[self.get_index(row, col)]
+    }
+
+    fn set_state(&mut self, row: usize, col: usize, state: u8) {
+        let idx = self.get_index(row, col);
+        self.current[idx] = state;
+
+        // Wake up the cell and its neighbours so the next tick re-evaluates them
+        self.active[idx] = true;
+        for &(dr, dc) in self.neighbor_deltas().iter() {
+            if let Some(neighbor_idx) = self.neighbor_index(row, col, dr, dc) {
+                self.active[neighbor_idx] = true;
+            }
+        }
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        Box::new(
+            self.current
+                .iter()
+                .enumerate()
+                .filter(|&(_, &cell)| cell == 1)
+                .map(move |(idx, _)| (idx / self.width, idx % self.width)),
+        )
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn render(&self) -> String {
+        Universe::render(self)
+    }
+}
@@ -0,0 +1,235 @@
+//! Charts of results files, drawn with plotters: total time against grid size, time per 10
+//! generations, memory and speedup over the naive engine
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use wasm_game_of_life::bench::EngineResult;
+use wasm_game_of_life::utils::ResultsFile;
+
+use crate::cli::{ChartFormat, ENGINES};
+
+const CHART_SIZE: (u32, u32) = (1024, 640);
+
+type ChartResult = Result<(), Box<dyn Error>>;
+
+/// Points of one line, labelled with its engine
+type Series = (String, Vec<(f64, f64)>);
+
+/// Draws a chart with one line per engine; the axis ranges decide whether the axes are logarithmic
+fn line_chart<DB, X, Y>(
+    root: &DrawingArea<DB, Shift>,
+    title: &str,
+    (x_desc, y_desc): (&str, &str),
+    x_range: X,
+    y_range: Y,
+    series: &[Series],
+) -> ChartResult
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+    X: AsRangedCoord<Value = f64>,
+    Y: AsRangedCoord<Value = f64>,
+    X::CoordDescType: ValueFormatter<f64>,
+    Y::CoordDescType: ValueFormatter<f64>,
+{
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(root)
+        .caption(title, ("sans-serif", 24))
+        .margin(20)
+        .x_label_area_size(45)
+        .y_label_area_size(80)
+        .build_cartesian_2d(x_range, y_range)?;
+    chart
+        .configure_mesh()
+        .x_desc(x_desc)
+        .y_desc(y_desc)
+        .x_labels(8)
+        .x_label_formatter(&tick_label)
+        .y_label_formatter(&tick_label)
+        .draw()?;
+
+    for (index, (engine, points)) in series.iter().enumerate() {
+        // Engines keep their colour from chart to chart
+        let color = Palette99::pick(ENGINES.iter().position(|name| name == engine).unwrap_or(ENGINES.len() + index)).to_rgba();
+        chart
+            .draw_series(LineSeries::new(points.iter().copied(), color.stroke_width(2)))?
+            .label(engine.as_str())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2)));
+        // Single points would not show as lines
+        chart.draw_series(points.iter().map(|&point| Circle::new(point, 3, color.filled())))?;
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+/// Axis label of a value: whole numbers without decimals, others with at most two
+fn tick_label(value: &f64) -> String {
+    if value.fract() == 0.0 || value.abs() >= 100.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.2}", value).trim_end_matches('0').to_string()
+    }
+}
+
+/// Range covering `values`, padded so that the extreme points are not drawn on the frame.
+/// On a logarithmic axis only positive values can be shown.
+fn axis_range(values: impl Iterator<Item = f64>, log: bool) -> Range<f64> {
+    let (min, max) = values
+        .filter(|value| value.is_finite() && (!log || *value > 0.0))
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| (min.min(value), max.max(value)));
+    if min > max {
+        return if log { 1.0..10.0 } else { 0.0..1.0 };
+    }
+    if log {
+        min / 1.5..max * 1.5
+    } else {
+        let padding = if max > min { (max - min) * 0.05 } else { 1.0 };
+        (min - padding).min(0.0)..max + padding
+    }
+}
+
+/// Draws `series` into an image file, with logarithmic axes where asked
+fn save_line_chart(path: &Path, format: ChartFormat, title: &str, descs: (&str, &str), log_x: bool, log_y: bool, series: &[Series]) -> ChartResult {
+    let x_range = axis_range(series.iter().flat_map(|(_, points)| points.iter().map(|point| point.0)), log_x);
+    let y_range = axis_range(series.iter().flat_map(|(_, points)| points.iter().map(|point| point.1)), log_y);
+    // Grid sizes rarely span several powers of ten, so a logarithmic x axis is labelled at the data
+    let mut x_values: Vec<f64> = series.iter().flat_map(|(_, points)| points.iter().map(|point| point.0)).collect();
+    x_values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    x_values.dedup();
+
+    // Each backend and axis kind is a different chart type
+    macro_rules! draw {
+        ($root:expr) => {{
+            let root = $root.into_drawing_area();
+            match (log_x, log_y) {
+                (true, true) => {
+                    line_chart(&root, title, descs, x_range.log_scale().with_key_points(x_values), y_range.log_scale(), series)
+                }
+                (true, false) => line_chart(&root, title, descs, x_range.log_scale().with_key_points(x_values), y_range, series),
+                (false, true) => line_chart(&root, title, descs, x_range, y_range.log_scale(), series),
+                (false, false) => line_chart(&root, title, descs, x_range, y_range, series),
+            }
+        }};
+    }
+    match format {
+        ChartFormat::Png => draw!(BitMapBackend::new(path, CHART_SIZE)),
+        ChartFormat::Svg => draw!(SVGBackend::new(path, CHART_SIZE)),
+    }
+}
+
+/// Results of each engine, in the order the engines first appear in the file
+fn by_engine<'a>(results: &[&'a EngineResult]) -> Vec<(String, Vec<&'a EngineResult>)> {
+    let mut engines: Vec<(String, Vec<&EngineResult>)> = Vec::new();
+    for &result in results {
+        match engines.iter_mut().find(|(engine, _)| *engine == result.engine) {
+            Some((_, engine_results)) => engine_results.push(result),
+            None => engines.push((result.engine.clone(), vec![result])),
+        }
+    }
+    engines
+}
+
+/// One line per engine of `value` against the number of cells in the grid
+fn against_cells<F>(results: &[&EngineResult], value: F) -> Vec<Series>
+where
+    F: Fn(&EngineResult) -> Option<f64>,
+{
+    by_engine(results)
+        .into_iter()
+        .map(|(engine, engine_results)| {
+            let mut points: Vec<(f64, f64)> = engine_results
+                .into_iter()
+                .filter_map(|result| Some(((result.width * result.height) as f64, value(result)?)))
+                .collect();
+            points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            (engine, points)
+        })
+        .filter(|(_, points)| !points.is_empty())
+        .collect()
+}
+
+/// Writes the charts of one results file into `out_dir`, named after `stem`, and returns their paths
+pub fn render_charts(results: &ResultsFile, stem: &str, out_dir: &str, format: ChartFormat) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    fs::create_dir_all(out_dir)?;
+    let path = |name: &str| Path::new(out_dir).join(format!("{}_{}.{}", stem, name, format.extension()));
+    let info = &results.info;
+    let run = format!("{} - {} - {} - {} generations", info.pattern, info.rule, info.topology, info.iterations);
+    let cells = "Cells in the grid";
+    let all: Vec<&EngineResult> = results.results.iter().collect();
+    let mut written = Vec::new();
+
+    // Total time against grid size
+    let times = against_cells(&all, |result| Some(result.summary().mean / 1e6));
+    let chart = path("time_vs_size");
+    save_line_chart(&chart, format, &format!("Mean total time - {}", run), (cells, "Time (ms)"), true, true, &times)?;
+    written.push(chart);
+
+    // Time of every 10 generations on each grid size
+    let mut sizes: BTreeMap<(usize, String), Vec<&EngineResult>> = BTreeMap::new();
+    for &result in &all {
+        sizes.entry((result.width * result.height, result.size())).or_default().push(result);
+    }
+    for ((_, size), size_results) in &sizes {
+        let series: Vec<Series> = size_results
+            .iter()
+            .map(|result| {
+                let points = result
+                    .generation_medians()
+                    .chunks(10)
+                    .enumerate()
+                    .map(|(block, times)| ((block * 10) as f64, times.iter().sum::<u64>() as f64 / 1e6))
+                    .collect();
+                (result.engine.clone(), points)
+            })
+            .collect();
+        let chart = path(&format!("{}_per_10_generations", size));
+        let title = format!("Time per 10 generations - {} - {} grid", info.pattern, size);
+        save_line_chart(&chart, format, &title, ("Generation", "Time (ms)"), false, false, &series)?;
+        written.push(chart);
+    }
+
+    // Memory: the heap when allocations were counted, else the resident set
+    let heap_counted = all.iter().all(|result| result.peak_memory().heap.is_some());
+    let memory = against_cells(&all, |result| {
+        let memory = result.peak_memory();
+        match memory.heap {
+            Some(heap) if heap_counted => Some(heap.peak_bytes as f64 / (1024.0 * 1024.0)),
+            _ => Some(memory.peak_rss_kb as f64 / 1024.0),
+        }
+    });
+    let chart = path("memory");
+    let what = if heap_counted { "Peak heap" } else { "Peak resident set" };
+    save_line_chart(&chart, format, &format!("{} - {}", what, run), (cells, "Memory (MB)"), true, false, &memory)?;
+    written.push(chart);
+
+    // Speedup over the naive engine on the same grid
+    let naive: BTreeMap<(usize, usize), f64> = all
+        .iter()
+        .filter(|result| result.engine == "Naive")
+        .map(|result| ((result.width, result.height), result.summary().mean))
+        .collect();
+    if naive.is_empty() {
+        println!("No speedup chart for {}: the naive engine was not run", stem);
+    } else {
+        let others: Vec<&EngineResult> = all.iter().copied().filter(|result| result.engine != "Naive").collect();
+        let speedups = against_cells(&others, |result| Some(naive.get(&(result.width, result.height))? / result.summary().mean));
+        let chart = path("speedup");
+        let title = format!("Speedup over the naive engine - {}", run);
+        save_line_chart(&chart, format, &title, (cells, "Speedup (x)"), true, true, &speedups)?;
+        written.push(chart);
+    }
+    Ok(written)
+}
@@ -0,0 +1,387 @@
+#![allow(dead_code)]
+use std::fmt;
+use std::str::FromStr;
+
+/// Hensel letters distinguishing the neighbourhood shapes for each live neighbour count
+const HENSEL_LETTERS: [&str; 9] = ["", "ce", "ceaikn", "ceaiknjqry", "ceaiknjqrytwz", "ceaiknjqry", "ceaikn", "ce", ""];
+
+/// One 3x3 neighbourhood per letter of counts 0 to 4, in the order of `HENSEL_LETTERS`.
+/// Bit `3 * row + col` is set for a live neighbour; counts 5 to 8 are the complements of 3 to 0.
+const HENSEL_NEIGHBOURHOODS: [&[u16]; 5] = [
+    &[0],
+    &[1, 2],
+    &[5, 10, 3, 40, 33, 68],
+    &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+    &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108],
+];
+
+/// The eight neighbour bits of a 3x3 neighbourhood, i.e. everything but the centre (bit 4)
+const NEIGHBOURS_MASK: u16 = 0b1_1110_1111;
+const CENTRE: u16 = 1 << 4;
+
+/// Neighbourhood shapes selected for each neighbour count, indexed like `HENSEL_LETTERS`
+type LetterSets = [[bool; 13]; 9];
+
+/// Life-like rule in B/S notation, e.g. `B3/S23` for Conway's Life, or an isotropic
+/// non-totalistic rule in Hensel notation such as `B2-a/S12`.
+///
+/// Every rule is compiled to a 512-entry lookup table indexed by the 3x3 neighbourhood
+/// (bit `3 * row + col`, the cell itself being bit 4). Outer-totalistic rules also keep
+/// `birth[n]` / `survival[n]` so engines that only count neighbours can evaluate them.
+///
+/// Generations rules such as Brian's Brain (`B2/S/C3`) have more than two states:
+/// 0 is dead, 1 alive, and a live cell that does not survive decays through the
+/// dying states `2..states` before becoming dead. Only live cells count as neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rule {
+    birth: [bool; 9],
+    survival: [bool; 9],
+    table: [u64; 8],
+    totalistic: bool,
+    states: u16,
+}
+
+/// Largest number of states a Generations rule may have, so states fit in a `u8`
+pub const MAX_STATES: u16 = 256;
+
+/// Reasons a rule string can be rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleError {
+    /// The string is not of the form `Bxx/Sxx`, `Sxx/Bxx` or `xx/xx`
+    Malformed(String),
+    /// A neighbour count outside `0..=8`
+    InvalidDigit(char),
+    /// A Hensel letter that does not exist for the neighbour count it follows
+    InvalidLetter { count: usize, letter: char },
+    /// A Generations state count that is not a number in `2..=256`
+    InvalidStates(String),
+    /// Births on zero neighbours would fill the infinite background, which the
+    /// sparse and Hashlife engines cannot represent
+    BirthOnZero,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::Malformed(rule) => write!(f, "malformed rule string '{}', expected B/S notation", rule),
+            RuleError::InvalidDigit(c) => write!(f, "invalid neighbour count '{}' in rule, expected 0-8", c),
+            RuleError::InvalidLetter { count, letter } => {
+                write!(f, "invalid Hensel letter '{}' for {} neighbours", letter, count)
+            }
+            RuleError::InvalidStates(states) => {
+                write!(f, "invalid number of states '{}', expected 2-{}", states, MAX_STATES)
+            }
+            RuleError::BirthOnZero => write!(f, "B0 rules are not supported"),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl Rule {
+    /// Conway's Game of Life, B3/S23
+    pub fn conway() -> Rule {
+        Rule::new(&[3], &[2, 3])
+    }
+
+    /// Builds a rule from the neighbour counts that cause birth and survival. Panics on
+    /// counts above 8, and on birth on zero neighbours, which `parse` rejects as `BirthOnZero`.
+    pub fn new(birth: &[usize], survival: &[usize]) -> Rule {
+        assert!(
+            birth.iter().chain(survival).all(|&n| n <= 8),
+            "Neighbour counts must be 0 to 8, got B{:?}/S{:?}",
+            birth,
+            survival
+        );
+        assert!(!birth.contains(&0), "{}", RuleError::BirthOnZero);
+        let all_letters = |counts: &[usize]| {
+            let mut sets = [[false; 13]; 9];
+            for &n in counts {
+                sets[n] = [true; 13];
+            }
+            sets
+        };
+        Rule::from_letter_sets(&all_letters(birth), &all_letters(survival))
+    }
+
+    /// Compiles per-count letter sets into the neighbourhood lookup table
+    fn from_letter_sets(birth: &LetterSets, survival: &LetterSets) -> Rule {
+        let mut table = [0u64; 8];
+        for index in 0..512u16 {
+            let (count, letter) = classify(index & NEIGHBOURS_MASK);
+            let sets = if index & CENTRE != 0 { survival } else { birth };
+            if sets[count][letter] {
+                table[index as usize / 64] |= 1 << (index % 64);
+            }
+        }
+
+        // Totalistic if every count selects either all of its shapes or none of them
+        let shapes = |n: usize| HENSEL_LETTERS[n].len().max(1);
+        let uniform = |sets: &LetterSets| (0..9).all(|n| sets[n][..shapes(n)].iter().all(|&s| s == sets[n][0]));
+        let totalistic = uniform(birth) && uniform(survival);
+
+        Rule {
+            birth: [0, 1, 2, 3, 4, 5, 6, 7, 8].map(|n| birth[n][0]),
+            survival: [0, 1, 2, 3, 4, 5, 6, 7, 8].map(|n| survival[n][0]),
+            table,
+            totalistic,
+            states: 2,
+        }
+    }
+
+    /// Same birth and survival conditions with `states` cell states (Generations rule)
+    pub fn with_states(mut self, states: u16) -> Rule {
+        assert!((2..=MAX_STATES).contains(&states), "Generations rules need 2 to {} states", MAX_STATES);
+        self.states = states;
+        self
+    }
+
+    /// Parses `B3/S23`, the legacy `S23/B3` form, the bare `23/3` (survival/birth) form,
+    /// or Hensel notation where a count is followed by the letters it includes (`B2a`)
+    /// or, after a minus sign, the letters it excludes (`B2-a`).
+    ///
+    /// A third part gives the number of states of a Generations rule, as in `B2/S/C3`
+    /// or the legacy survival/birth/states form `/2/3`.
+    pub fn parse(rule: &str) -> Result<Rule, RuleError> {
+        let malformed = || RuleError::Malformed(rule.to_string());
+        let parts: Vec<&str> = rule.trim().split('/').collect();
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(malformed());
+        }
+
+        let states = match parts.get(2).map(|part| part.trim()) {
+            Some(part) => {
+                let count = part.strip_prefix(|c: char| c == 'C' || c == 'c' || c == 'G' || c == 'g').unwrap_or(part);
+                match count.parse::<u16>() {
+                    Ok(states) if (2..=MAX_STATES).contains(&states) => states,
+                    _ => return Err(RuleError::InvalidStates(part.to_string())),
+                }
+            }
+            None => 2,
+        };
+
+        let (mut birth, mut survival) = (None, None);
+        for (i, part) in parts[..2].iter().enumerate() {
+            let part = part.trim();
+            let (kind, spec) = match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('B') => ('B', &part[1..]),
+                Some('S') => ('S', &part[1..]),
+                // Bare digits follow the legacy survival/birth order
+                _ => (if i == 0 { 'S' } else { 'B' }, part),
+            };
+            let sets = Rule::parse_letter_sets(spec).map_err(|e| e.unwrap_or_else(malformed))?;
+            let slot = if kind == 'B' { &mut birth } else { &mut survival };
+            if slot.replace(sets).is_some() {
+                return Err(malformed());
+            }
+        }
+
+        let (birth, survival) = match (birth, survival) {
+            (Some(birth), Some(survival)) => (birth, survival),
+            _ => return Err(malformed()),
+        };
+        if birth[0][0] {
+            return Err(RuleError::BirthOnZero);
+        }
+        Ok(Rule::from_letter_sets(&birth, &survival).with_states(states))
+    }
+
+    /// Parses the counts and letters of one half of a rule; `Err(None)` means malformed
+    fn parse_letter_sets(spec: &str) -> Result<LetterSets, Option<RuleError>> {
+        let mut sets = [[false; 13]; 9];
+        let mut chars = spec.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let count = match c.to_digit(10) {
+                Some(n) if n <= 8 => n as usize,
+                _ => return Err(Some(RuleError::InvalidDigit(c))),
+            };
+            let letters = HENSEL_LETTERS[count];
+            let negated = chars.next_if_eq(&'-').is_some();
+
+            let mut selected = [false; 13];
+            let mut any_letter = false;
+            while let Some(letter) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                match letters.find(letter) {
+                    Some(i) => selected[i] = true,
+                    None => return Err(Some(RuleError::InvalidLetter { count, letter })),
+                }
+                any_letter = true;
+            }
+
+            if negated && !any_letter {
+                return Err(None);
+            }
+            for (i, set) in sets[count].iter_mut().enumerate().take(letters.len().max(1)) {
+                *set = if !any_letter { true } else { selected[i] != negated };
+            }
+        }
+        Ok(sets)
+    }
+
+    /// True for outer-totalistic rules, which depend only on the number of live neighbours
+    pub fn is_totalistic(&self) -> bool {
+        self.totalistic
+    }
+
+    /// Number of cell states, 2 for Life-like rules
+    pub fn states(&self) -> u16 {
+        self.states
+    }
+
+    /// True for Generations rules, whose cells have dying states
+    pub fn is_generations(&self) -> bool {
+        self.states > 2
+    }
+
+    /// Next state of a cell in state `state` (0 dead, 1 alive, 2.. dying) with the given
+    /// number of live neighbours. Only meaningful for outer-totalistic rules.
+    #[inline]
+    pub fn next_cell_state(&self, state: u8, live_neighbors: usize) -> u8 {
+        match state {
+            0 => self.birth[live_neighbors] as u8,
+            1 if self.survival[live_neighbors] => 1,
+            _ => self.decay(state),
+        }
+    }
+
+    /// Next state of a cell in state `state` from its 3x3 neighbourhood of live cells
+    #[inline]
+    pub fn next_cell_state_neighbourhood(&self, state: u8, index: usize) -> u8 {
+        match state {
+            0 | 1 if self.next_state_neighbourhood(index) => 1,
+            0 => 0,
+            _ => self.decay(state),
+        }
+    }
+
+    /// State following a live or dying cell that does not survive
+    #[inline]
+    fn decay(&self, state: u8) -> u8 {
+        if (state as u16) + 1 < self.states {
+            state + 1
+        } else {
+            0
+        }
+    }
+
+    /// Next state of a cell given whether it is alive and its live neighbour count.
+    /// Only meaningful for outer-totalistic rules.
+    #[inline]
+    pub fn next_state(&self, alive: bool, live_neighbors: usize) -> bool {
+        if alive {
+            self.survival[live_neighbors]
+        } else {
+            self.birth[live_neighbors]
+        }
+    }
+
+    /// Next state of a cell from its 3x3 neighbourhood, bit `3 * row + col` being set
+    /// for each live cell and bit 4 for the cell itself
+    #[inline]
+    pub fn next_state_neighbourhood(&self, index: usize) -> bool {
+        self.table[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn is_birth(&self, live_neighbors: usize) -> bool {
+        self.birth[live_neighbors]
+    }
+
+    pub fn is_survival(&self, live_neighbors: usize) -> bool {
+        self.survival[live_neighbors]
+    }
+
+    /// Writes one half of the rule in canonical Hensel notation
+    fn fmt_half(&self, f: &mut fmt::Formatter, centre: u16) -> fmt::Result {
+        for (count, letters) in HENSEL_LETTERS.iter().enumerate() {
+            let shapes = letters.len().max(1);
+            let selected: Vec<bool> = (0..shapes)
+                .map(|i| self.next_state_neighbourhood((representative(count, i) | centre) as usize))
+                .collect();
+            let included: String = letters.chars().zip(&selected).filter(|&(_, &s)| s).map(|(c, _)| c).collect();
+            let selected = selected.iter().filter(|&&s| s).count();
+
+            if selected == 0 {
+                continue;
+            }
+            write!(f, "{}", count)?;
+            if selected < shapes {
+                // Use whichever of the inclusive or exclusive forms is shorter
+                let excluded: String = letters.chars().filter(|&c| !included.contains(c)).collect();
+                if excluded.len() < included.len() {
+                    write!(f, "-{}", excluded)?;
+                } else {
+                    write!(f, "{}", included)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Example neighbourhood for the `letter`-th shape of `count` live neighbours
+fn representative(count: usize, letter: usize) -> u16 {
+    if count <= 4 {
+        HENSEL_NEIGHBOURHOODS[count][letter]
+    } else {
+        NEIGHBOURS_MASK ^ HENSEL_NEIGHBOURHOODS[8 - count][letter]
+    }
+}
+
+/// Neighbour count and shape index of a neighbourhood, matching it against the
+/// representatives under all rotations and reflections
+fn classify(neighbours: u16) -> (usize, usize) {
+    let count = neighbours.count_ones() as usize;
+    let canonical = canonical_form(neighbours);
+    let shape = (0..HENSEL_LETTERS[count].len().max(1))
+        .find(|&i| canonical_form(representative(count, i)) == canonical)
+        .expect("every neighbourhood belongs to a Hensel class");
+    (count, shape)
+}
+
+/// Smallest of the eight symmetric images of a 3x3 neighbourhood
+fn canonical_form(neighbourhood: u16) -> u16 {
+    let transform = |mask: u16, map: &dyn Fn(usize, usize) -> (usize, usize)| {
+        let mut out = 0;
+        for bit in (0..9).filter(|&bit| mask & (1 << bit) != 0) {
+            let (row, col) = map(bit / 3, bit % 3);
+            out |= 1 << (3 * row + col);
+        }
+        out
+    };
+
+    let mut best = u16::MAX;
+    let mut image = neighbourhood;
+    for _ in 0..4 {
+        image = transform(image, &|row, col| (col, 2 - row));
+        best = best.min(image).min(transform(image, &|row, col| (row, 2 - col)));
+    }
+    best
+}
+
+impl Default for Rule {
+    fn default() -> Rule {
+        Rule::conway()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Rule, RuleError> {
+        Rule::parse(s)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "B")?;
+        self.fmt_half(f, 0)?;
+        write!(f, "/S")?;
+        self.fmt_half(f, CENTRE)?;
+        if self.is_generations() {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
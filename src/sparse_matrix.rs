@@ -1,16 +1,9 @@
 #![allow(dead_code)]
 use wasm_bindgen::prelude::*;
 use std::collections::{HashMap, HashSet};
-extern crate sysinfo;
-use sysinfo::{System, SystemExt}; 
-
-// Function to print memory usage
-fn print_memory_usage(label: &str) {
-    let mut sys = System::new_all();
-    sys.refresh_memory();
-    let memory_used = sys.used_memory();
-    println!("{} - Memory Usage: {} MB", label, memory_used / 1024);
-}
+use crate::traits::LifeUniverse;
+use crate::rules::Rule;
+use crate::topology::Topology;
 
 #[wasm_bindgen]
 #[repr(u8)]
@@ -24,11 +17,14 @@ pub enum Cell {
 pub struct Universe {
     width: usize,
     height: usize,
-    live_cells: HashSet<(usize, usize)>,
+    live_cells: HashSet<(i64, i64)>,    // Signed so an unbounded plane can grow past the viewport
+    dying: HashMap<(i64, i64), u8>,     // Dying cells of Generations rules and their state
+    rule: Rule,
+    topology: Topology,
 }
 
 impl Universe {
-    fn get_neighbors(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+    fn get_neighbors(&self, row: i64, col: i64) -> Vec<(i64, i64)> {
         let deltas: [isize; 3] = [-1, 0, 1];
         let mut neighbors = Vec::new();
 
@@ -37,19 +33,62 @@ impl Universe {
                 if delta_row == 0 && delta_col == 0 {
                     continue;
                 }
-                let neighbor_row = ((row as isize + delta_row + self.height as isize) % self.height as isize) as usize;
-                let neighbor_col = ((col as isize + delta_col + self.width as isize) % self.width as isize) as usize;
-                neighbors.push((neighbor_row, neighbor_col));
+                if !self.topology.is_bounded() {
+                    neighbors.push((row + delta_row as i64, col + delta_col as i64));
+                } else if let Some((neighbor_row, neighbor_col)) =
+                    self.topology.neighbour(self.width, self.height, row as usize, col as usize, delta_row, delta_col)
+                {
+                    neighbors.push((neighbor_row as i64, neighbor_col as i64));
+                }
             }
         }
         neighbors
     }
+
+    /// Whether `(row, col)` lies on the `width x height` grid
+    fn in_viewport(&self, (row, col): (i64, i64)) -> bool {
+        row >= 0 && col >= 0 && row < self.height as i64 && col < self.width as i64
+    }
+
+    /// Returns whether the cell at signed `(row, col)` is alive; off-grid cells
+    /// only exist on an unbounded plane
+    pub fn get_cell_at(&self, row: i64, col: i64) -> bool {
+        self.live_cells.contains(&(row, col))
+    }
+
+    /// Sets the cell at signed `(row, col)` to alive or dead
+    pub fn set_cell_at(&mut self, row: i64, col: i64, alive: bool) {
+        self.dying.remove(&(row, col));
+        if alive {
+            self.live_cells.insert((row, col));
+        } else {
+            self.live_cells.remove(&(row, col));
+        }
+    }
+
+    /// Signed coordinates of every live cell, including any outside the viewport
+    pub fn all_live_cells(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.live_cells.iter().copied()
+    }
+
+    /// Number of live cells, including any outside the viewport
+    pub fn total_population(&self) -> u64 {
+        self.live_cells.len() as u64
+    }
+
+    /// Signed `(top, left, bottom, right)` bounds of the live cells, inclusive
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        self.live_cells.iter().fold(None, |bounds, &(row, col)| match bounds {
+            None => Some((row, col, row, col)),
+            Some((top, left, bottom, right)) => Some((top.min(row), left.min(col), bottom.max(row), right.max(col))),
+        })
+    }
 }
 
 #[wasm_bindgen]
 impl Universe {
     pub fn tick(&mut self) {
-        let mut neighbor_counts: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut neighbor_counts: HashMap<(i64, i64), usize> = HashMap::new();
 
         for &(row, col) in &self.live_cells {
             for neighbor in self.get_neighbors(row, col) {
@@ -59,23 +98,59 @@ impl Universe {
 
         let mut next_live_cells = HashSet::new();
 
+        // Isolated live cells never show up in the counts, so S0 has to keep them explicitly
+        if self.rule.is_survival(0) {
+            for &cell in &self.live_cells {
+                if !neighbor_counts.contains_key(&cell) {
+                    next_live_cells.insert(cell);
+                }
+            }
+        }
+
         for (cell, count) in neighbor_counts {
-            if count == 3 || (count == 2 && self.live_cells.contains(&cell)) {
+            // Dying cells are neither dead nor alive, so they can't be born
+            if !self.dying.is_empty() && self.dying.contains_key(&cell) {
+                continue;
+            }
+            if self.rule.next_state(self.live_cells.contains(&cell), count) {
                 next_live_cells.insert(cell);
             }
         }
 
+        if self.rule.is_generations() {
+            let mut next_dying = HashMap::new();
+
+            // Dying cells move on to their next state, or vanish after the last one
+            for (&cell, &state) in &self.dying {
+                let next_state = self.rule.next_cell_state(state, 0);
+                if next_state != 0 {
+                    next_dying.insert(cell, next_state);
+                }
+            }
+
+            // Live cells that did not survive start dying
+            for &cell in &self.live_cells {
+                if !next_live_cells.contains(&cell) {
+                    next_dying.insert(cell, 2);
+                }
+            }
+            self.dying = next_dying;
+        }
+
         self.live_cells = next_live_cells;
     }
 
     pub fn new_with_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
         let mut live_cells = HashSet::new();
+        let mut dying = HashMap::new();
 
         for (index, &value) in flat_matrix.iter().enumerate() {
+            let row = (index / width) as i64;
+            let col = (index % width) as i64;
             if value == 1 {
-                let row = index / width;
-                let col = index % width;
                 live_cells.insert((row, col));
+            } else if value > 1 {
+                dying.insert((row, col), value);
             }
         }
 
@@ -83,6 +158,9 @@ impl Universe {
             width,
             height,
             live_cells,
+            dying,
+            rule: Rule::default(),
+            topology: Topology::default(),
         }
     }
 
@@ -97,7 +175,7 @@ impl Universe {
         let mut buffer = String::new();
         for row in 0..self.height {
             for col in 0..self.width {
-                if self.live_cells.contains(&(row, col)) {
+                if self.live_cells.contains(&(row as i64, col as i64)) {
                     buffer.push('■');
                 } else {
                     buffer.push('□');
@@ -117,3 +195,121 @@ impl fmt::Display for Universe {
     }
 }
 
+impl LifeUniverse for Universe {
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Universe {
+        Universe::new_with_matrix(width, height, flat_matrix)
+    }
+
+    fn from_live_cells(width: usize, height: usize, live_cells: Vec<(usize, usize)>) -> Universe {
+        Universe {
+            width,
+            height,
+            live_cells: live_cells.into_iter().map(|(row, col)| (row as i64, col as i64)).collect(),
+            dying: HashMap::new(),
+            rule: Rule::default(),
+            topology: Topology::default(),
+        }
+    }
+
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn supports_rule(&self, rule: &Rule) -> bool {
+        // Only neighbour counts are available; dying cells are kept in their own map
+        rule.is_totalistic()
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        assert!(self.supports_rule(&rule), "The sparse engine does not support the rule {}", rule);
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn supports_topology(&self, _topology: Topology) -> bool {
+        true
+    }
+
+    fn set_topology(&mut self, topology: Topology) {
+        assert!(self.supports_topology(topology), "The sparse engine does not support the {} topology", topology);
+        topology.check_size(self.width, self.height);
+        self.topology = topology;
+
+        // Cells that grew past the viewport of an unbounded plane do not exist on a bounded grid
+        if topology.is_bounded() {
+            let (width, height) = (self.width as i64, self.height as i64);
+            let on_grid = |&(row, col): &(i64, i64)| row >= 0 && col >= 0 && row < height && col < width;
+            self.live_cells.retain(on_grid);
+            self.dying.retain(|cell, _| on_grid(cell));
+        }
+    }
+
+    fn tick(&mut self) {
+        Universe::tick(self)
+    }
+
+    fn get_cell(&self, row: usize, col: usize) -> bool {
+        self.live_cells.contains(&(row as i64, col as i64))
+    }
+
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool) {
+        self.set_state(row, col, alive as u8);
+    }
+
+    fn get_state(&self, row: usize, col: usize) -> u8 {
+        let (row, col) = (row as i64, col as i64);
+        if self.live_cells.contains(&(row, col)) {
+            1
+        } else {
+            self.dying.get(&(row, col)).copied().unwrap_or(0)
+        }
+    }
+
+    fn set_state(&mut self, row: usize, col: usize, state: u8) {
+        let (row, col) = (row as i64, col as i64);
+        self.live_cells.remove(&(row, col));
+        self.dying.remove(&(row, col));
+        match state {
+            0 => (),
+            1 => {
+                self.live_cells.insert((row, col));
+            }
+            _ => {
+                self.dying.insert((row, col), state);
+            }
+        }
+    }
+
+    fn population(&self) -> usize {
+        if self.topology.is_bounded() {
+            self.live_cells.len()
+        } else {
+            self.live_cells().count()
+        }
+    }
+
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_> {
+        // On an unbounded plane only the cells inside the viewport are reported
+        Box::new(
+            self.live_cells
+                .iter()
+                .filter(move |&&cell| self.in_viewport(cell))
+                .map(|&(row, col)| (row as usize, col as usize)),
+        )
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn render(&self) -> String {
+        Universe::render(self)
+    }
+}
@@ -0,0 +1,232 @@
+#![allow(dead_code)]
+use std::fmt;
+use std::str::FromStr;
+
+/// How the edges of a `width x height` grid are joined, following Golly's
+/// bounded grids (`B3/S23:T100,100` and friends).
+///
+/// - `Torus`: left joins right and top joins bottom.
+/// - `Plane`: nothing is joined, cells beyond the edges are always dead.
+/// - `KleinBottle`: left joins right, top joins bottom with a twist, i.e. crossing
+///   the top or bottom edge mirrors the column (Golly's `Kw*,h`).
+/// - `CrossSurface`: both pairs of edges are joined with a twist.
+/// - `Sphere`: top joins left and bottom joins right; the grid must be square.
+/// - `Unbounded`: an infinite plane on which the grid is only a viewport. Patterns
+///   grow freely past it, so only engines without a fixed-size grid support it.
+///
+/// Where both edges of a corner are twisted or glued together (cross-surface and
+/// sphere) the cell diagonally across the corner is dead, so corner cells have
+/// only seven neighbours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    #[default]
+    Torus,
+    Plane,
+    KleinBottle,
+    CrossSurface,
+    Sphere,
+    Unbounded,
+}
+
+/// Every topology, in declaration order
+pub const ALL_TOPOLOGIES: [Topology; 6] = [
+    Topology::Torus,
+    Topology::Plane,
+    Topology::KleinBottle,
+    Topology::CrossSurface,
+    Topology::Sphere,
+    Topology::Unbounded,
+];
+
+/// Reasons a topology name or Golly grid spec can be rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TopologyError {
+    /// Neither one of our names nor a Golly grid letter
+    Unknown(String),
+    /// A Golly grid we cannot model, with the reason why
+    Unsupported(String, &'static str),
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TopologyError::Unknown(name) => {
+                write!(f, "unknown topology '{}', expected torus, plane, klein, cross, sphere or unbounded", name)
+            }
+            TopologyError::Unsupported(spec, reason) => write!(f, "unsupported Golly grid '{}': {}", spec, reason),
+        }
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+impl Topology {
+    /// Whether the grid has edges at all, i.e. the topology is not `Unbounded`
+    pub fn is_bounded(self) -> bool {
+        self != Topology::Unbounded
+    }
+
+    /// Maps `(row, col)`, which may lie one cell outside the grid, to the grid cell
+    /// it is joined to, or `None` if it is beyond a dead edge. On an unbounded plane
+    /// such cells exist but are not part of the grid, so they map to `None` too.
+    #[inline]
+    pub fn wrap(self, width: usize, height: usize, row: isize, col: isize) -> Option<(usize, usize)> {
+        let (w, h) = (width as isize, height as isize);
+        let row_out = row < 0 || row >= h;
+        let col_out = col < 0 || col >= w;
+        if !row_out && !col_out {
+            return Some((row as usize, col as usize));
+        }
+
+        let (row, col) = match self {
+            Topology::Torus => (row.rem_euclid(h), col.rem_euclid(w)),
+            Topology::Plane | Topology::Unbounded => return None,
+            Topology::KleinBottle => {
+                let col = col.rem_euclid(w);
+                if row_out {
+                    (row.rem_euclid(h), w - 1 - col)
+                } else {
+                    (row, col)
+                }
+            }
+            Topology::CrossSurface => match (row_out, col_out) {
+                (true, true) => return None,
+                (true, false) => (row.rem_euclid(h), w - 1 - col),
+                _ => (h - 1 - row, col.rem_euclid(w)),
+            },
+            Topology::Sphere => {
+                if row_out && col_out {
+                    return None;
+                } else if row < 0 {
+                    (col, 0)
+                } else if row >= h {
+                    (col, w - 1)
+                } else if col < 0 {
+                    (0, row)
+                } else {
+                    (h - 1, row)
+                }
+            }
+        };
+        Some((row as usize, col as usize))
+    }
+
+    /// Neighbour of `(row, col)` at offset `(dr, dc)`, each within `-1..=1`
+    #[inline]
+    pub fn neighbour(self, width: usize, height: usize, row: usize, col: usize, dr: isize, dc: isize) -> Option<(usize, usize)> {
+        self.wrap(width, height, row as isize + dr, col as isize + dc)
+    }
+
+    /// Whether a `width x height` grid can have this topology
+    pub fn supports_size(self, width: usize, height: usize) -> bool {
+        width > 0 && height > 0 && (self != Topology::Sphere || width == height)
+    }
+
+    /// Panics unless a `width x height` grid can have this topology
+    pub fn check_size(self, width: usize, height: usize) {
+        assert!(
+            self.supports_size(width, height),
+            "A {}x{} grid cannot be a {}",
+            width,
+            height,
+            self
+        );
+    }
+
+    /// Parses the grid part of a Golly rule string, e.g. `T100,100` or `P64,32`. The size
+    /// is left to `golly_grid_size`, but grids we cannot model are rejected: shifted edges
+    /// such as `T100+5,80`, and Klein bottles other than `Kw*,h` whose top and bottom
+    /// edges are twisted.
+    pub fn from_golly(spec: &str) -> Result<Topology, TopologyError> {
+        let spec = spec.trim();
+        let mut chars = spec.chars();
+        let topology = match chars.next() {
+            Some('T') => Topology::Torus,
+            Some('P') => Topology::Plane,
+            Some('K') => Topology::KleinBottle,
+            Some('C') => Topology::CrossSurface,
+            Some('S') => Topology::Sphere,
+            _ => return Err(TopologyError::Unknown(spec.to_string())),
+        };
+
+        let dims = chars.as_str();
+        let unsupported = |reason| Err(TopologyError::Unsupported(spec.to_string(), reason));
+        if dims.contains(['+', '-']) {
+            return unsupported("shifted edges are not supported");
+        }
+        let (width, height) = dims.split_once(',').unwrap_or((dims, ""));
+        match (topology, width.trim().ends_with('*'), height.trim().ends_with('*')) {
+            (Topology::KleinBottle, false, true) => {
+                unsupported("only Klein bottles twisted on the top and bottom edges (Kw*,h) are supported")
+            }
+            (Topology::KleinBottle, false, false) if !dims.is_empty() => {
+                unsupported("a Klein bottle needs its twisted pair of edges marked with *")
+            }
+            (Topology::KleinBottle, true, true) => unsupported("a Klein bottle has only one twisted pair of edges"),
+            (Topology::KleinBottle, _, _) | (_, false, false) => Ok(topology),
+            _ => unsupported("only a Klein bottle has a twisted pair of edges"),
+        }
+    }
+
+    /// Grid size of a Golly grid spec such as `T100,100`, `K100*,80` or `S64`, if both
+    /// dimensions are given and not zero (Golly's infinite strips). Specs `from_golly`
+    /// rejects, such as shifted edges, have no size either.
+    pub fn golly_grid_size(spec: &str) -> Option<(usize, usize)> {
+        let dims: String = spec.trim().chars().skip(1).filter(|&c| c != '*').collect();
+        let (width, height) = match dims.split_once(',') {
+            Some((width, height)) => (width.trim().parse().ok()?, height.trim().parse().ok()?),
+            None => {
+                let side = dims.trim().parse().ok()?;
+                (side, side)
+            }
+        };
+        if width == 0 || height == 0 {
+            None
+        } else {
+            Some((width, height))
+        }
+    }
+
+    /// Golly grid spec of a `width x height` grid with this topology, e.g. `T100,100`,
+    /// or `None` on an unbounded plane which Golly does not need a suffix for
+    pub fn to_golly(self, width: usize, height: usize) -> Option<String> {
+        match self {
+            Topology::Torus => Some(format!("T{},{}", width, height)),
+            Topology::Plane => Some(format!("P{},{}", width, height)),
+            Topology::KleinBottle => Some(format!("K{}*,{}", width, height)),
+            Topology::CrossSurface => Some(format!("C{},{}", width, height)),
+            Topology::Sphere => Some(format!("S{}", width)),
+            Topology::Unbounded => None,
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = TopologyError;
+
+    fn from_str(s: &str) -> Result<Topology, TopologyError> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "torus" => Ok(Topology::Torus),
+            "plane" => Ok(Topology::Plane),
+            "klein" | "klein-bottle" => Ok(Topology::KleinBottle),
+            "cross" | "cross-surface" => Ok(Topology::CrossSurface),
+            "sphere" => Ok(Topology::Sphere),
+            "unbounded" | "infinite" => Ok(Topology::Unbounded),
+            _ => Topology::from_golly(s),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Topology::Torus => "torus",
+            Topology::Plane => "plane",
+            Topology::KleinBottle => "klein-bottle",
+            Topology::CrossSurface => "cross-surface",
+            Topology::Sphere => "sphere",
+            Topology::Unbounded => "unbounded",
+        };
+        write!(f, "{}", name)
+    }
+}
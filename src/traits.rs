@@ -1,4 +1,107 @@
-#![allow(dead_code)]
-pub trait TickUniv {
-    fn tick(&mut self);  // Define the common tick method
-}
\ No newline at end of file
+#![allow(dead_code)]
+use crate::rules::Rule;
+use crate::topology::Topology;
+
+/// Common interface implemented by every Game of Life engine, so callers can
+/// construct, advance and inspect any `Universe` without knowing which
+/// algorithm is behind it.
+///
+/// Coordinates are `(row, col)` pairs, matching the flat row-major matrices
+/// used throughout the crate.
+pub trait LifeUniverse {
+    /// Creates a universe from a flat row-major matrix of `width * height`
+    /// entries, where `1` is a live cell and `0` a dead one.
+    fn from_matrix(width: usize, height: usize, flat_matrix: Vec<u8>) -> Self
+    where
+        Self: Sized;
+
+    /// Creates a universe from a list of live `(row, col)` cells.
+    fn from_live_cells(width: usize, height: usize, live_cells: Vec<(usize, usize)>) -> Self
+    where
+        Self: Sized,
+    {
+        let mut flat_matrix = vec![0; width * height];
+        for (row, col) in live_cells {
+            flat_matrix[row * width + col] = 1;
+        }
+        Self::from_matrix(width, height, flat_matrix)
+    }
+
+    /// Creates a universe from a flat matrix that evolves under `rule`.
+    fn from_matrix_with_rule(width: usize, height: usize, flat_matrix: Vec<u8>, rule: Rule) -> Self
+    where
+        Self: Sized,
+    {
+        let mut universe = Self::from_matrix(width, height, flat_matrix);
+        universe.set_rule(rule);
+        universe
+    }
+
+    /// Rule the universe evolves under, B3/S23 unless set otherwise.
+    fn rule(&self) -> &Rule;
+
+    /// Whether the engine can evolve `rule`. By default only two-state
+    /// outer-totalistic rules are supported.
+    fn supports_rule(&self, rule: &Rule) -> bool {
+        rule.is_totalistic() && !rule.is_generations()
+    }
+
+    /// Replaces the rule. Panics if `supports_rule` rejects it.
+    fn set_rule(&mut self, rule: Rule);
+
+    /// How the grid edges are joined, a torus unless set otherwise.
+    fn topology(&self) -> Topology;
+
+    /// Whether the engine can evolve a grid with `topology`. By default only
+    /// bounded grids are supported.
+    fn supports_topology(&self, topology: Topology) -> bool {
+        topology.is_bounded()
+    }
+
+    /// Replaces the topology. Panics if `supports_topology` rejects it or the
+    /// grid cannot have it, e.g. a non-square sphere.
+    fn set_topology(&mut self, topology: Topology);
+
+    /// Advances the universe by one generation.
+    fn tick(&mut self);
+
+    /// Advances the universe by `n` generations.
+    fn step(&mut self, n: usize) {
+        for _ in 0..n {
+            self.tick();
+        }
+    }
+
+    /// Returns whether the cell at `(row, col)` is alive.
+    fn get_cell(&self, row: usize, col: usize) -> bool;
+
+    /// Sets the cell at `(row, col)` to alive or dead.
+    fn set_cell(&mut self, row: usize, col: usize, alive: bool);
+
+    /// State of the cell at `(row, col)`: 0 dead, 1 alive, 2 and up dying
+    /// under a Generations rule.
+    fn get_state(&self, row: usize, col: usize) -> u8 {
+        self.get_cell(row, col) as u8
+    }
+
+    /// Sets the state of the cell at `(row, col)`. Engines without dying
+    /// states treat any state but 1 as dead.
+    fn set_state(&mut self, row: usize, col: usize, state: u8) {
+        self.set_cell(row, col, state == 1)
+    }
+
+    /// Number of live cells.
+    fn population(&self) -> usize {
+        self.live_cells().count()
+    }
+
+    /// Iterates over the `(row, col)` coordinates of every live cell (state 1).
+    fn live_cells(&self) -> Box<dyn Iterator<Item = (usize, usize)> + '_>;
+
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    /// Renders the current state as text, one line per row.
+    fn render(&self) -> String;
+}
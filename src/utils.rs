@@ -1,12 +1,17 @@
 #![allow(dead_code)]
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
-use std::cmp;
-use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::convert::TryFrom;
+use std::fmt;
 
-use sysinfo::{System, SystemExt};
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{Deserialize, Serialize};
 
-use csv::Writer;
+use crate::rules::{Rule, RuleError};
+use crate::topology::{Topology, TopologyError};
+use crate::traits::LifeUniverse;
+use crate::bench::EngineResult;
+use crate::memory::HeapStats;
 
 pub fn set_panic_hook() {
     // When the `console_error_panic_hook` feature is enabled, we can call the
@@ -19,170 +24,994 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
-pub fn parse_header(line: &str) -> (usize, usize) {
-    // Extract x value from header line "x = N, y = N, rule = B3/S23"
-    let parts: Vec<&str> = line.split(',').collect();
-    let mut dims: (usize, usize) = (0, 0);
+/// Reasons an RLE, plaintext or Life 1.06/1.05 pattern can be rejected. Lines and columns are 1-based.
+#[derive(Debug)]
+pub enum RleError {
+    /// The file could not be opened or read
+    Io(io::Error),
+    /// No `x = N, y = N` header line before the cells
+    MissingHeader,
+    /// A header line that is not of the form `x = N, y = N, rule = R`
+    InvalidHeader { line: usize, column: usize, message: String },
+    /// A rule in the header that `Rule::parse` rejects
+    InvalidRule { line: usize, column: usize, error: RuleError },
+    /// A `#P`/`#R` line that is not of the form `#P x y`
+    InvalidMetadata { line: usize, column: usize, message: String },
+    /// A Golly grid suffix such as `:T64,64` with an unknown topology
+    InvalidTopology { line: usize, column: usize, error: TopologyError },
+    /// A character that is not a run count, cell state, `$` or `!`
+    UnexpectedChar { line: usize, column: usize, found: char },
+    /// A cell state the rule does not have, or above `yO` (255)
+    InvalidState { line: usize, column: usize, state: u16, states: u16 },
+    /// A run count, starting at `column`, too large to represent
+    RunCountTooLarge { line: usize, column: usize },
+    /// A row holding more cells than the pattern is wide
+    RowTooLong { line: usize, column: usize, length: usize, expected: usize },
+    /// A plaintext cell that is neither `.` nor `O`, or a Life 1.05 one neither `.` nor `*`
+    UnexpectedCell { line: usize, column: usize, found: char },
+    /// A Life 1.06 line that is not of the form `x y`, or a cell too far from the others
+    InvalidCoordinates { line: usize, column: usize, message: String },
+    /// More rows than the pattern is high
+    TooManyRows { line: usize, column: usize, rows: usize, expected: usize },
+    /// A rule the engine being seeded cannot evolve, e.g. Generations for Hashlife
+    UnsupportedRule { rule: Rule },
+    /// A `width x height` pattern or grid with more than `MAX_DENSE_CELLS` cells, or more
+    /// than fit in memory at all, asked for as a flat matrix
+    TooManyCells { width: usize, height: usize },
+    /// A `pattern` of `(x, y)` cells that does not fit a `grid` of `(width, height)`
+    /// cells, centered or with its top-left corner at `offset` `(row, col)`
+    PatternTooLarge { pattern: (usize, usize), grid: (usize, usize), offset: Option<(usize, usize)> },
+}
+
+impl RleError {
+    /// Line and column the error was found at, if it points into the file
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match *self {
+            RleError::Io(_)
+            | RleError::MissingHeader
+            | RleError::UnsupportedRule { .. }
+            | RleError::TooManyCells { .. }
+            | RleError::PatternTooLarge { .. } => None,
+            RleError::InvalidHeader { line, column, .. }
+            | RleError::InvalidMetadata { line, column, .. }
+            | RleError::InvalidRule { line, column, .. }
+            | RleError::InvalidTopology { line, column, .. }
+            | RleError::UnexpectedChar { line, column, .. }
+            | RleError::InvalidState { line, column, .. }
+            | RleError::RunCountTooLarge { line, column }
+            | RleError::UnexpectedCell { line, column, .. }
+            | RleError::InvalidCoordinates { line, column, .. }
+            | RleError::RowTooLong { line, column, .. }
+            | RleError::TooManyRows { line, column, .. } => Some((line, column)),
+        }
+    }
+}
+
+impl fmt::Display for RleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((line, column)) = self.position() {
+            write!(f, "line {}, column {}: ", line, column)?;
+        }
+        match self {
+            RleError::Io(e) => write!(f, "{}", e),
+            RleError::MissingHeader => write!(f, "missing 'x = N, y = N' header line"),
+            RleError::InvalidHeader { message, .. }
+            | RleError::InvalidMetadata { message, .. }
+            | RleError::InvalidCoordinates { message, .. } => write!(f, "{}", message),
+            RleError::InvalidRule { error, .. } => write!(f, "{}", error),
+            RleError::InvalidTopology { error, .. } => write!(f, "{}", error),
+            RleError::UnexpectedChar { found, .. } => {
+                write!(f, "unexpected character '{}', expected a run count, b, o, ., a state letter, $ or !", found)
+            }
+            RleError::InvalidState { state, states, .. } if *state > u8::MAX as u16 => {
+                write!(f, "state {} is beyond the largest state yO ({}) of a rule with {} states", state, u8::MAX, states)
+            }
+            RleError::InvalidState { state, states, .. } => {
+                write!(f, "state {} does not exist in a rule with {} states", state, states)
+            }
+            RleError::RunCountTooLarge { .. } => write!(f, "run count is too large"),
+            RleError::UnexpectedCell { found, .. } => write!(f, "unexpected character '{}', expected ., O or *", found),
+            RleError::RowTooLong { length, expected, .. } => {
+                write!(f, "row has {} cells, expected at most {}", length, expected)
+            }
+            RleError::TooManyRows { rows, expected, .. } => {
+                write!(f, "pattern has {} rows, expected at most {}", rows, expected)
+            }
+            RleError::UnsupportedRule { rule } => write!(f, "the rule {} is not supported by this engine", rule),
+            RleError::TooManyCells { width, height } => {
+                write!(f, "{}x{} cells are too many for a flat matrix, at most {} are allowed", width, height, MAX_DENSE_CELLS)
+            }
+            RleError::PatternTooLarge { pattern, grid, offset } => {
+                write!(f, "{}x{} pattern ", pattern.0, pattern.1)?;
+                if let Some((row, col)) = offset {
+                    write!(f, "at row {}, column {} ", row, col)?;
+                }
+                write!(f, "does not fit a {}x{} grid", grid.0, grid.1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RleError::Io(e) => Some(e),
+            RleError::InvalidRule { error, .. } => Some(error),
+            RleError::InvalidTopology { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RleError {
+    fn from(e: io::Error) -> RleError {
+        RleError::Io(e)
+    }
+}
+
+/// Everything an RLE header line "x = N, y = N, rule = B3/S23:P64,64" declares
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RleHeader {
+    pub width: usize,               // x
+    pub height: usize,              // y
+    pub rule: Option<Rule>,         // Rule entry, if the header has one
+    pub topology: Option<Topology>, // Golly bounded grid appended to the rule, if any
+}
+
+/// Parses the header line found at `line_number` of the file
+pub fn parse_header(line: &str, line_number: usize) -> Result<RleHeader, RleError> {
+    let invalid = |column: usize, message: String| RleError::InvalidHeader { line: line_number, column, message };
+
+    // Golly grid sizes such as ":T64,32" contain a comma, so the rule entry is taken whole
+    let (dims_part, rule_start) = match line.find("rule") {
+        Some(start) => (&line[..start], Some(start)),
+        None => (line, None),
+    };
+
+    let mut dims: (Option<usize>, Option<usize>) = (None, None);
+    let mut offset = 0;
+    for part in dims_part.split(',') {
+        let column = offset + part.len() - part.trim_start().len() + 1;
+        offset += part.len() + 1;
+        if part.trim().is_empty() {
+            continue;
+        }
+
+        let (key, value) = part.split_once('=').ok_or_else(|| invalid(column, format!("expected 'key = value', found '{}'", part.trim())))?;
+        let parsed = value.trim().parse().map_err(|_| invalid(column, format!("invalid {} value '{}'", key.trim(), value.trim())));
+        match key.trim() {
+            "x" => dims.0 = Some(parsed?),
+            "y" => dims.1 = Some(parsed?),
+            _ => (),
+        }
+    }
+
+    let (width, height) = match dims {
+        (Some(width), Some(height)) => (width, height),
+        (None, _) => return Err(invalid(1, "missing x in header".to_string())),
+        (_, None) => return Err(invalid(1, "missing y in header".to_string())),
+    };
 
-    for part in parts {
+    let mut header = RleHeader { width, height, rule: None, topology: None };
+    if let Some(start) = rule_start {
+        let after_key = &line[start + "rule".len()..];
+        let value = after_key
+            .trim_start()
+            .strip_prefix('=')
+            .ok_or_else(|| invalid(start + 1, "expected 'rule = ...'".to_string()))?;
+        let value_column = line.len() - value.trim_start().len() + 1;
+        let value = value.trim();
 
-        let kv: Vec<&str> = part.trim().split('=').collect();
-        
-        if kv.len() == 2 {
-            match kv[0].trim(){
-                "x" => dims.0 = kv[1].trim().parse().unwrap(),
-                "y" => dims.1 = kv[1].trim().parse().unwrap(),
-                _ => ()
-            } 
+        // Golly appends the bounded grid after a colon, e.g. "B3/S23:T100,100"
+        let (rule, grid) = match value.split_once(':') {
+            Some((rule, grid)) => (rule.trim(), Some(grid)),
+            None => (value, None),
+        };
+        header.rule = Some(
+            Rule::parse(rule)
+                .map_err(|error| RleError::InvalidRule { line: line_number, column: value_column, error })?,
+        );
+        if let Some(grid) = grid {
+            let column = value_column + rule.len() + 1;
+            header.topology = Some(
+                Topology::from_golly(grid)
+                    .map_err(|error| RleError::InvalidTopology { line: line_number, column, error })?,
+            );
         }
     }
-    dims
+    Ok(header)
 }
 
-pub fn iter_coords<F>(boardrow: &str, dims: (usize, usize), func: &mut F)
+/// RLE token of a cell state, the inverse of what `iter_coords` reads. Two-state
+/// patterns use `b`/`o`, multi-state patterns `.` for dead and `A`, `B`, ... `pA`, ...
+pub fn state_to_rle(state: u8, multi_state: bool) -> String {
+    match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (_, true) => {
+            let prefix = (state - 1) / 24;
+            let letter = (b'A' + (state - 1) % 24) as char;
+            if prefix == 0 {
+                letter.to_string()
+            } else {
+                format!("{}{}", (b'p' + prefix - 1) as char, letter)
+            }
+        }
+    }
+}
+
+/// Decodes the cell lines of an RLE file, which start at `first_line`, calling `func`
+/// with `(row, col, length, state)` for every run of cells as written in the file. Cells
+/// not covered by any run are dead, and states must be below the rule's `states`. Unlike
+/// `iter_coords` this never visits the whole `x` by `y` rectangle, so huge sparse
+/// patterns cost time in their runs only.
+pub fn iter_runs<F>(boardrow: &str, dims: (usize, usize), states: u16, first_line: usize, func: &mut F) -> Result<(), RleError>
 where
-    F: FnMut(u8)
+    F: FnMut(usize, usize, usize, u8)
 {
-    let mut prefixnum: i64 = 0;
+    let mut prefixnum: usize = 0;
     let mut prefixset = false;
-    let mut row_width  = 0;
-    let mut row_count = 0;
-    let width = cmp::max(dims.0, dims.1);
-    
+    let mut prefix_column = 0;
+    let mut row_width: usize = 0;
+    let mut row_count: usize = 0;
+    let mut state_prefix: u16 = 0;
+    let (width, height) = dims;
+    let (mut line, mut column) = (first_line, 0);
+
     for c in boardrow.chars() {
-        if c.is_numeric() {
-            prefixnum = prefixnum * 10 + c.to_digit(10).unwrap() as i64;
+        column += 1;
+        let unexpected = RleError::UnexpectedChar { line, column, found: c };
+
+        if state_prefix != 0 && !c.is_ascii_uppercase() {
+            // pA..yO cells need their second letter right away
+            return Err(unexpected);
+        }
+
+        if c.is_ascii_digit() {
+            if !prefixset {
+                prefix_column = column;
+            }
+            prefixnum = prefixnum
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(c.to_digit(10).unwrap() as usize))
+                .ok_or(RleError::RunCountTooLarge { line, column: prefix_column })?;
             prefixset = true;
+        } else if ('p'..='y').contains(&c) {
+            // First letter of a multi-state cell from state 25 up (pA..yO), the run count carries over
+            state_prefix = (c as u16 - 'p' as u16 + 1) * 24;
         } else {
-            let repeat = if prefixset { prefixnum } else { 1 } as usize;
-            if c == 'b' {
-                for _ in 0..repeat {
-                    func(0);
-                    row_width += 1;
+            let repeat = if prefixset { prefixnum } else { 1 };
+
+            // Two-state files use b/o, multi-state files use . for dead and A..X for states 1 to 24
+            let state = match c {
+                'b' | '.' => Some(0),
+                'o' => Some(1),
+                'A'..='X' => Some(state_prefix + (c as u16 - 'A' as u16) + 1),
+                _ => None,
+            };
+            state_prefix = 0;
+
+            if let Some(state) = state {
+                if state >= states {
+                    return Err(RleError::InvalidState { line, column, state, states });
                 }
-            } else if c == 'o' {
-                for _ in 0..repeat {
-                    func(1);
-                    row_width += 1;
+                let state = state as u8;
+                if row_count >= height {
+                    return Err(RleError::TooManyRows { line, column, rows: row_count + 1, expected: height });
                 }
-            } else if c == '$' {
-
-                // Number of processed rows
-                row_count += repeat;
-
-                // Count how many blanks are needed to pad this row
-                let row_pad = width - row_width;
-                match row_pad.cmp(&0) {
-                    Ordering::Equal => {row_width = 0;
-                                    continue; 
-                                    },
-                    Ordering::Greater => {
-                        for _ in 0..row_pad{ func(0) }
-                        },
-                    Ordering::Less => {
-                        panic!("RLE file incorrectly formatted, row has {} entries - expected {}", row_width, width)
-                        }
+                if row_width.saturating_add(repeat) > width {
+                    return Err(RleError::RowTooLong { line, column, length: row_width.saturating_add(repeat), expected: width });
                 }
-                row_width = 0;
-
-                // If there are multiple line jumps, then add repeat empty lines:
-                for _ in 0..width*(repeat-1) {
-                    func(0);
+                if repeat > 0 {
+                    func(row_count, row_width, repeat, state);
                 }
-
+                row_width += repeat;
+            } else if c == '$' {
+                // Jumps past the last row only skip empty space, so they are ignored
+                row_count = row_count.saturating_add(repeat.max(1)).min(height);
+                row_width = 0;
             } else if c == '!' {
-
-                // Add missing padding
-                let total = width*row_count + row_width;
-                let n_pad =  width*width - total;
-                
-                if n_pad==0{
-                    break;
-                } else {
-                    for _ in 0..n_pad {
-                        func(0);
-                    }
-                }
-                row_width=0;
-            } else if c == '\n' || c == '\r' {
-                // do nothing on line break
+                break;
+            } else if c == '\n' {
+                line += 1;
+                column = 0;
+                continue;
+            } else if c == '\r' || c == ' ' || c == '\t' {
+                // whitespace between tokens is allowed
                 continue;
             } else {
-                panic!("RLE file incorrectly formatted, only 'b' and 'o' allowed.")
+                return Err(unexpected);
             }
             prefixset = false;
             prefixnum = 0;
         }
-        // Verify that format is correct
-        if row_count>width{
-            panic!("RLE file incorrectly formatted, too many rows in file");
+    }
+    Ok(())
+}
+
+/// Decodes the cell lines of an RLE file, which start at `first_line`, calling `func`
+/// with the state of every cell of the `x` by `y` rectangle holding the pattern, row by row
+pub fn iter_coords<F>(boardrow: &str, dims: (usize, usize), states: u16, first_line: usize, func: &mut F) -> Result<(), RleError>
+where
+    F: FnMut(u8)
+{
+    let width = dims.0;
+    let mut emitted = 0;
+    iter_runs(boardrow, dims, states, first_line, &mut |row, col, length, state| {
+        // Blanks skipped by line ends, then the run itself
+        let start = row * width + col;
+        for _ in emitted..start {
+            func(0);
+        }
+        for _ in 0..length {
+            func(state);
+        }
+        emitted = start + length;
+    })?;
+
+    // Pad the rest of the pattern with blanks
+    for _ in emitted..dims.0 * dims.1 {
+        func(0);
+    }
+    Ok(())
+}
+
+/// Descriptive information of an RLE pattern, from its `#` lines and header
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatternMetadata {
+    pub name: Option<String>,         // #N
+    pub author: Option<String>,       // #O
+    pub comments: Vec<String>,        // #C and #c, plus any other # line verbatim
+    pub offset: Option<(i64, i64)>,   // #P or #R: (x, y) of the top-left corner
+    pub rule: Rule,                   // Header rule, else #r, else B3/S23
+    pub topology: Option<Topology>,   // Golly bounded grid appended to the header rule
+}
+
+impl PatternMetadata {
+    /// Name to label results with: the `#N` name, else `fallback` (e.g. the file name)
+    pub fn label(&self, fallback: &str) -> String {
+        self.name.clone().unwrap_or_else(|| fallback.to_string())
+    }
+}
+
+/// Most cells a flat matrix of cell states may hold, 4 GiB at a byte per cell. Larger
+/// patterns can still be read, and go to the engines as live cells.
+pub const MAX_DENSE_CELLS: usize = 1 << 32;
+
+/// Length of a flat `width x height` matrix, or an error if it would be too large
+pub fn dense_len(width: usize, height: usize) -> Result<usize, RleError> {
+    width
+        .checked_mul(height)
+        .filter(|&cells| cells <= MAX_DENSE_CELLS)
+        .ok_or(RleError::TooManyCells { width, height })
+}
+
+/// A decoded pattern, whatever file format it came from. Only the runs of non-dead cells
+/// are kept, so the size of the header alone costs no memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub width: usize,                          // x
+    pub height: usize,                         // y
+    pub runs: Vec<(usize, usize, usize, u8)>, // `(row, col, length, state)` of runs of non-dead cells, row by row
+    pub metadata: PatternMetadata,
+}
+
+impl Pattern {
+    /// `(row, col)` of every live cell, for `LifeUniverse::from_live_cells`
+    pub fn live_cells(&self) -> Vec<(usize, usize)> {
+        self.runs
+            .iter()
+            .filter(|&&(_, _, _, state)| state == 1)
+            .flat_map(|&(row, col, length, _)| (col..col + length).map(move |col| (row, col)))
+            .collect()
+    }
+
+    /// `width * height` cell states, row by row, or an error if the pattern is too large
+    pub fn cells(&self) -> Result<Vec<u8>, RleError> {
+        self.place((self.width, self.height), Placement::At { row: 0, col: 0 })
+    }
+
+    /// Flat `width x height` matrix with the pattern at `placement`, built from the runs
+    /// without going through a matrix of the pattern
+    pub fn place(&self, (width, height): (usize, usize), placement: Placement) -> Result<Vec<u8>, RleError> {
+        let (row_offset, col_offset) = placement_offset((self.width, self.height), (width, height), placement)?;
+        let mut cells = vec![0; dense_len(width, height)?];
+        for &(row, col, length, state) in &self.runs {
+            let start = (row + row_offset) * width + col + col_offset;
+            cells[start..start + length].fill(state);
         }
+        Ok(cells)
     }
 }
 
-/// Read .rle file and return problem parameters
-pub fn init_from_file(file_path: &str, width: usize) -> Vec<u8> {
-    // Reads the file and saves as a string
-    let f = BufReader::new(File::open(file_path).unwrap());
-    let mut line_iter = f.lines();
-    
-    // Skip comments
-    let mut header_line = String::new();
-    while let Some(Ok(line)) = line_iter.next() {
-        if !line.starts_with('#') {
-            header_line = line;
-            break;
+/// Adds the `#` line found at `line_number` to the metadata
+fn parse_metadata_line(line: &str, line_number: usize, metadata: &mut PatternMetadata) -> Result<(), RleError> {
+    let mut chars = line.chars();
+    chars.next(); // '#'
+    let kind = chars.next();
+    let rest = chars.as_str().trim();
+    let invalid = |message: String| RleError::InvalidMetadata { line: line_number, column: 1, message };
+
+    match kind {
+        Some('N') => metadata.name = Some(rest.to_string()),
+        Some('O') => metadata.author = Some(rest.to_string()),
+        Some('C') | Some('c') => metadata.comments.push(rest.to_string()),
+        Some('P') | Some('R') => {
+            let coords: Vec<i64> = rest
+                .split_whitespace()
+                .map(|value| value.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid(format!("invalid offset '{}', expected 'x y'", rest)))?;
+            match coords[..] {
+                [x, y] => metadata.offset = Some((x, y)),
+                _ => return Err(invalid(format!("invalid offset '{}', expected 'x y'", rest))),
+            }
+        }
+        Some('r') => {
+            metadata.rule = Rule::parse(rest)
+                .map_err(|error| RleError::InvalidRule { line: line_number, column: 4, error })?;
         }
+        _ => metadata.comments.push(line.to_string()),
     }
+    Ok(())
+}
 
-    // Parse the grid size from header
-    let dims = parse_header(&header_line);
+/// Parses the text of a .rle file, calling `func` with `(row, col, length, state)` for
+/// each run of cells as `iter_runs` does, and returns the pattern size and metadata.
+/// `#` lines before the pattern become metadata; anything after the terminating `!` is ignored.
+pub fn parse_rle_runs<F>(text: &str, func: &mut F) -> Result<(usize, usize, PatternMetadata), RleError>
+where
+    F: FnMut(usize, usize, usize, u8)
+{
+    let mut metadata = PatternMetadata::default();
+    let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line));
 
-    // Convert RLE to binary vector to represent initial grid
-    let mut rle_str = String::new();
-    while let Some(line) = line_iter.next() {
-        rle_str.push_str(&line.unwrap());
+    // Metadata lines (and blank lines) come first, then the header
+    let (header_line, header) = loop {
+        let (line_number, line) = lines.next().ok_or(RleError::MissingHeader)?;
+        if line.starts_with('#') {
+            parse_metadata_line(line, line_number, &mut metadata)?;
+        } else if !line.trim().is_empty() {
+            break (line_number, parse_header(line, line_number)?);
+        }
+    };
+    metadata.topology = header.topology;
+
+    // Keep the line breaks so errors can point at the right line; stray # lines are blanked
+    let mut body = String::new();
+    for (line_number, line) in lines {
+        if line.starts_with('#') {
+            parse_metadata_line(line, line_number, &mut metadata)?;
+        } else {
+            body.push_str(line);
+        }
+        body.push('\n');
+    }
+    if let Some(rule) = header.rule {
+        metadata.rule = rule;
     }
 
-    let mut init_state: Vec<u8> = Vec::new();
-    
-    // Return initial grid as a N^2 sized vector
-    iter_coords(rle_str.as_str(), dims, &mut |p| {
-        init_state.push(p);
-    });
+    iter_runs(body.as_str(), (header.width, header.height), metadata.rule.states(), header_line + 1, func)?;
+    Ok((header.width, header.height, metadata))
+}
+
+/// Parses the text of a .rle file into a pattern, see `parse_rle_runs`
+pub fn parse_rle(text: &str) -> Result<Pattern, RleError> {
+    let mut runs = Vec::new();
+    let (width, height, metadata) = parse_rle_runs(text, &mut |row, col, length, state| {
+        if state != 0 {
+            runs.push((row, col, length, state));
+        }
+    })?;
+    Ok(Pattern { width, height, runs, metadata })
+}
+
+/// Parses a LifeWiki plaintext (.cells) file: `!` comment lines, then one line of `.`
+/// and `O` per row. `!Name:` and `!Author:` comments fill in the metadata.
+pub fn parse_plaintext(text: &str) -> Result<Pattern, RleError> {
+    let mut metadata = PatternMetadata::default();
+    let mut rows: Vec<(usize, &str)> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            let comment = comment.trim();
+            if let Some(name) = comment.strip_prefix("Name:") {
+                metadata.name = Some(name.trim().to_string());
+            } else if let Some(author) = comment.strip_prefix("Author:") {
+                metadata.author = Some(author.trim().to_string());
+            } else {
+                metadata.comments.push(comment.to_string());
+            }
+        } else {
+            rows.push((index + 1, line));
+        }
+    }
+    // Blank lines in the middle are empty rows, the ones at the end are not part of the pattern
+    while rows.last().is_some_and(|(_, row)| row.is_empty()) {
+        rows.pop();
+    }
+
+    let width = rows.iter().map(|(_, row)| row.chars().count()).max().unwrap_or(0);
+    let height = rows.len();
+    let mut runs: Vec<(usize, usize, usize, u8)> = Vec::new();
+    for (row, &(line_number, line)) in rows.iter().enumerate() {
+        for (col, c) in line.chars().enumerate() {
+            match c {
+                '.' => {}
+                'O' | '*' => match runs.last_mut() {
+                    Some((run_row, run_col, length, _)) if *run_row == row && *run_col + *length == col => *length += 1,
+                    _ => runs.push((row, col, 1, 1)),
+                },
+                found => return Err(RleError::UnexpectedCell { line: line_number, column: col + 1, found }),
+            }
+        }
+    }
+    Ok(Pattern { width, height, runs, metadata })
+}
+
+/// Bounding box `(min x, min y, max x, max y)` of the cells so far grown to take in `(x, y)`,
+/// or `None` when the cells would be further apart than a `usize` can count
+fn widen_bounds(bounds: Option<(i64, i64, i64, i64)>, x: i64, y: i64) -> Option<(i64, i64, i64, i64)> {
+    let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((x, y, x, y));
+    let (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+    let fits = |span: Option<i64>| span.is_some_and(|span| (span as u64) < usize::MAX as u64);
+    (fits(max_x.checked_sub(min_x)) && fits(max_y.checked_sub(min_y))).then_some((min_x, min_y, max_x, max_y))
+}
+
+/// Pattern of the `(x, y)` live cells within `bounds`, cropped to them with the coordinates
+/// of its top-left corner kept as the metadata offset
+fn crop_live_cells(
+    live_cells: Vec<(i64, i64)>,
+    bounds: Option<(i64, i64, i64, i64)>,
+    mut metadata: PatternMetadata,
+) -> Pattern {
+    let Some((min_x, min_y, max_x, max_y)) = bounds else {
+        return Pattern { width: 0, height: 0, runs: Vec::new(), metadata };
+    };
+    let width = (max_x - min_x) as usize + 1;
+    let height = (max_y - min_y) as usize + 1;
+    let mut runs: Vec<(usize, usize, usize, u8)> =
+        live_cells.into_iter().map(|(x, y)| ((y - min_y) as usize, (x - min_x) as usize, 1, 1)).collect();
+    runs.sort_unstable();
+    runs.dedup();
+    metadata.offset = Some((min_x, min_y));
+    Pattern { width, height, runs, metadata }
+}
+
+/// Parses a Life 1.06 file: a `#Life 1.06` line, then one `x y` line per live cell. The
+/// pattern is cropped to the live cells, and the coordinates of its top-left corner are
+/// kept as the metadata offset.
+pub fn parse_life106(text: &str) -> Result<Pattern, RleError> {
+    let mut metadata = PatternMetadata::default();
+    let mut live_cells: Vec<(i64, i64)> = Vec::new();
+    let mut bounds = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("#Life") {
+            continue;
+        }
+        if trimmed.starts_with('#') {
+            parse_metadata_line(trimmed, line_number, &mut metadata)?;
+            continue;
+        }
+        let coords: Result<Vec<i64>, _> = trimmed.split_whitespace().map(|value| value.parse()).collect();
+        let column = line.len() - line.trim_start().len() + 1;
+        let (x, y) = match coords.as_deref() {
+            Ok(&[x, y]) => (x, y),
+            _ => {
+                let message = format!("invalid cell coordinates '{}', expected 'x y'", trimmed);
+                return Err(RleError::InvalidCoordinates { line: line_number, column, message });
+            }
+        };
+        // Cells at both ends of the i64 range are further apart than a usize can count
+        bounds = Some(widen_bounds(bounds, x, y).ok_or_else(|| {
+            let message = format!("cell '{}' is too far from the others", trimmed);
+            RleError::InvalidCoordinates { line: line_number, column, message }
+        })?);
+        live_cells.push((x, y));
+    }
+    Ok(crop_live_cells(live_cells, bounds, metadata))
+}
+
+/// Parses a Life 1.05 file: a `#Life 1.05` line, then blocks of `.` and `*` rows, each
+/// opened by a `#P x y` line giving the coordinates of its top-left cell. `#D` lines are
+/// comments, and `#N` (Conway's rules) or `#R survival/birth` set the rule. The pattern
+/// is cropped to the live cells like a Life 1.06 one.
+pub fn parse_life105(text: &str) -> Result<Pattern, RleError> {
+    let mut metadata = PatternMetadata::default();
+    let mut live_cells: Vec<(i64, i64)> = Vec::new();
+    let mut bounds = None;
+    let (mut block_x, mut block_y, mut row) = (0i64, 0i64, 0i64);
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with("#Life") {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            let rest = comment.get(1..).unwrap_or("").trim();
+            match comment.chars().next() {
+                Some('D') => metadata.comments.push(rest.to_string()),
+                Some('N') => metadata.rule = Rule::conway(),
+                Some('R') => {
+                    metadata.rule = Rule::parse(rest)
+                        .map_err(|error| RleError::InvalidRule { line: line_number, column: 4, error })?;
+                }
+                Some('P') => {
+                    let coords: Result<Vec<i64>, _> = rest.split_whitespace().map(|value| value.parse()).collect();
+                    (block_x, block_y) = match coords.as_deref() {
+                        Ok(&[x, y]) => (x, y),
+                        _ => {
+                            let message = format!("invalid block position '{}', expected 'x y'", rest);
+                            return Err(RleError::InvalidMetadata { line: line_number, column: 1, message });
+                        }
+                    };
+                    row = 0;
+                }
+                _ => metadata.comments.push(line.to_string()),
+            }
+            continue;
+        }
+
+        for (col, c) in line.chars().enumerate() {
+            match c {
+                '.' => {}
+                '*' => {
+                    let cell = block_x.checked_add(col as i64).zip(block_y.checked_add(row));
+                    let widened = cell.and_then(|(x, y)| Some((x, y, widen_bounds(bounds, x, y)?)));
+                    let Some((x, y, widened)) = widened else {
+                        let message = "cell is too far from the others".to_string();
+                        return Err(RleError::InvalidCoordinates { line: line_number, column: col + 1, message });
+                    };
+                    bounds = Some(widened);
+                    live_cells.push((x, y));
+                }
+                found => return Err(RleError::UnexpectedCell { line: line_number, column: col + 1, found }),
+            }
+        }
+        row += 1;
+    }
+    Ok(crop_live_cells(live_cells, bounds, metadata))
+}
+
+/// Pattern file formats we can read and write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternFormat {
+    /// Run length encoded, `.rle`
+    Rle,
+    /// LifeWiki plaintext, `.cells`
+    Plaintext,
+    /// Life 1.06 coordinate list, `.lif` or `.life`
+    Life106,
+    /// Life 1.05 blocks of `.` and `*`, also `.lif`; told apart from 1.06 by its first
+    /// line. Only read: universes are written as Life 1.06 instead.
+    Life105,
+}
+
+impl PatternFormat {
+    /// Format implied by the extension of `file_path`, if it is one we know
+    pub fn from_extension(file_path: &str) -> Option<PatternFormat> {
+        let extension = std::path::Path::new(file_path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "rle" => Some(PatternFormat::Rle),
+            "cells" => Some(PatternFormat::Plaintext),
+            "lif" | "life" => Some(PatternFormat::Life106),
+            _ => None,
+        }
+    }
+
+    /// Format recognised from the first meaningful line of `text`, if it is unambiguous
+    pub fn sniff(text: &str) -> Option<PatternFormat> {
+        let line = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+        if line.starts_with("#Life 1.06") {
+            Some(PatternFormat::Life106)
+        } else if line.starts_with("#Life 1.05") {
+            Some(PatternFormat::Life105)
+        } else if line.starts_with('!') || line.chars().all(|c| matches!(c, '.' | 'O' | '*')) {
+            Some(PatternFormat::Plaintext)
+        } else if line.starts_with('#') || line.starts_with('x') {
+            // RLE files open with comments or the header; a Life 1.06 file without
+            // its `#Life` line is ambiguous and left to the extension
+            let header = text.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#'));
+            header.filter(|header| header.starts_with('x')).map(|_| PatternFormat::Rle)
+        } else {
+            None
+        }
+    }
+
+    /// Format of the file at `file_path` holding `text`: the content when it gives the
+    /// format away, else the extension, else RLE
+    pub fn detect(file_path: &str, text: &str) -> PatternFormat {
+        PatternFormat::sniff(text)
+            .or_else(|| PatternFormat::from_extension(file_path))
+            .unwrap_or(PatternFormat::Rle)
+    }
+}
+
+/// Parses `text` as a pattern in the given format
+pub fn parse_pattern(text: &str, format: PatternFormat) -> Result<Pattern, RleError> {
+    match format {
+        PatternFormat::Rle => parse_rle(text),
+        PatternFormat::Plaintext => parse_plaintext(text),
+        PatternFormat::Life106 => parse_life106(text),
+        PatternFormat::Life105 => parse_life105(text),
+    }
+}
+
+/// Reads and parses a .rle, .cells, Life 1.06 or Life 1.05 file, detecting its format
+pub fn read_pattern(file_path: &str) -> Result<Pattern, RleError> {
+    let text = fs::read_to_string(file_path)?;
+    parse_pattern(&text, PatternFormat::detect(file_path, &text))
+}
+
+/// Runs `(row, col, length, state)` of equal non-dead states in a `width`-wide flat matrix,
+/// row by row
+fn matrix_runs(cells: &[u8], width: usize) -> Vec<(usize, usize, usize, u8)> {
+    let mut runs: Vec<(usize, usize, usize, u8)> = Vec::new();
+    for (idx, &state) in cells.iter().enumerate().filter(|&(_, &state)| state != 0) {
+        let (row, col) = (idx / width, idx % width);
+        match runs.last_mut() {
+            Some((run_row, run_col, length, run_state)) if *run_row == row && *run_col + *length == col && *run_state == state => {
+                *length += 1
+            }
+            _ => runs.push((row, col, 1, state)),
+        }
+    }
+    runs
+}
+
+/// Runs of non-dead cells of an engine's grid, see `matrix_runs`. Two-state universes are
+/// read from their live cells, so a grid of any size can be encoded. Dying states of
+/// Generations rules only show through `get_state`, so those grids are read cell by cell
+/// and must fit in `MAX_DENSE_CELLS`.
+fn universe_runs(universe: &dyn LifeUniverse) -> Vec<(usize, usize, usize, u8)> {
+    let (width, height) = (universe.width(), universe.height());
+    if universe.rule().states() > 2 {
+        let len = dense_len(width, height).unwrap_or_else(|e| panic!("Cannot encode the dying cells: {}", e));
+        let mut cells = vec![0; len];
+        for row in 0..height {
+            for col in 0..width {
+                cells[row * width + col] = universe.get_state(row, col);
+            }
+        }
+        return matrix_runs(&cells, width);
+    }
+
+    let mut live: Vec<(usize, usize)> = universe.live_cells().collect();
+    live.sort_unstable();
+    let mut runs: Vec<(usize, usize, usize, u8)> = Vec::new();
+    for (row, col) in live {
+        match runs.last_mut() {
+            Some((run_row, run_col, length, _)) if *run_row == row && *run_col + *length == col => *length += 1,
+            _ => runs.push((row, col, 1, 1)),
+        }
+    }
+    runs
+}
 
-    // Embed initial state in the middle of NxN grid
-    let grid_size = (init_state.len() as f64).sqrt().floor() as usize;
+/// Inclusive `(top, left, bottom, right)` bounds of row-major runs, or `None` if there are none
+fn runs_box(runs: &[(usize, usize, usize, u8)]) -> Option<(usize, usize, usize, usize)> {
+    let (first, last) = (runs.first()?, runs.last()?);
+    let left = runs.iter().map(|&(_, col, _, _)| col).min()?;
+    let right = runs.iter().map(|&(_, col, length, _)| col + length - 1).max()?;
+    Some((first.0, left, last.0, right))
+}
+
+/// Column RLE lines are wrapped at, as Golly does
+const RLE_LINE_WIDTH: usize = 70;
+
+/// Appends an RLE token to `body`, starting a new line when it would not fit
+fn push_rle_token(body: &mut String, line_length: &mut usize, count: usize, symbol: &str) {
+    let token = if count > 1 { format!("{}{}", count, symbol) } else { symbol.to_string() };
+    if *line_length + token.len() > RLE_LINE_WIDTH {
+        body.push('\n');
+        *line_length = 0;
+    }
+    body.push_str(&token);
+    *line_length += token.len();
+}
 
-    let n_offset = calc_padding(width, grid_size);
+/// Encodes the row-major runs of non-dead cells of a `width x height` grid as RLE, see
+/// `encode_rle`
+fn runs_to_rle(runs: &[(usize, usize, usize, u8)], width: usize, height: usize, rule: &Rule, topology: Topology) -> String {
+    let multi_state = rule.states() > 2;
 
-    let mut output_mat = vec![0; width*width];
+    // Bounding box of every cell that is not dead, dying ones included
+    let bounds = runs_box(runs);
+    let (pattern_width, pattern_height) =
+        bounds.map_or((0, 0), |(top, left, bottom, right)| (right - left + 1, bottom - top + 1));
 
-    // Copy the input matrix to the center of the result matrix
-    for i in 0..grid_size {
-        for j in 0..grid_size {
-            let source_idx = i * grid_size + j;
-            let target_idx = (i + n_offset) * width + (j + n_offset);
-            output_mat[target_idx] = init_state[source_idx];
+    let mut text = format!("x = {}, y = {}, rule = {}", pattern_width, pattern_height, rule);
+    if let Some(grid) = topology.to_golly(width, height) {
+        text.push(':');
+        text.push_str(&grid);
+    }
+    text.push('\n');
+
+    let mut body = String::new();
+    let mut line_length = 0;
+    if let Some((top, left, _, _)) = bounds {
+        let (mut row, mut col) = (top, left);
+        for &(run_row, run_col, length, state) in runs {
+            // Blank rows collapse into one `n$`, and trailing dead cells are implied by the end of the row
+            if run_row > row {
+                push_rle_token(&mut body, &mut line_length, run_row - row, "$");
+                (row, col) = (run_row, left);
+            }
+            if run_col > col {
+                push_rle_token(&mut body, &mut line_length, run_col - col, &state_to_rle(0, multi_state));
+            }
+            push_rle_token(&mut body, &mut line_length, length, &state_to_rle(state, multi_state));
+            col = run_col + length;
         }
     }
+    push_rle_token(&mut body, &mut line_length, 1, "!");
+
+    text.push_str(&body);
+    text.push('\n');
+    text
+}
+
+/// Encodes a `width x height` flat matrix of cell states as RLE, cropped to the bounding
+/// box of the non-dead cells. Bounded topologies are written as a Golly grid suffix so
+/// the pattern re-opens with the same edges.
+pub fn encode_rle(cells: &[u8], width: usize, height: usize, rule: &Rule, topology: Topology) -> String {
+    assert_eq!(cells.len(), width * height, "Expected {} cells for a {}x{} grid", width * height, width, height);
+    runs_to_rle(&matrix_runs(cells, width), width, height, rule, topology)
+}
+
+/// Encodes the current generation of any engine as RLE, see `encode_rle`. On an
+/// unbounded plane only the viewport is saved.
+pub fn universe_to_rle(universe: &dyn LifeUniverse) -> String {
+    let (width, height) = (universe.width(), universe.height());
+    runs_to_rle(&universe_runs(universe), width, height, universe.rule(), universe.topology())
+}
+
+/// Saves the current generation of any engine to a .rle file
+pub fn write_rle(universe: &dyn LifeUniverse, file_path: &str) -> io::Result<()> {
+    fs::write(file_path, universe_to_rle(universe))
+}
+
+/// Encodes the live cells among row-major runs as plaintext, see `encode_plaintext`
+fn runs_to_plaintext(runs: &[(usize, usize, usize, u8)], name: Option<&str>) -> String {
+    let live: Vec<(usize, usize, usize, u8)> = runs.iter().copied().filter(|&(_, _, _, state)| state == 1).collect();
+    let mut text = String::new();
+    if let Some(name) = name {
+        text.push_str(&format!("!Name: {}\n", name));
+    }
+    if let Some((top, left, bottom, _)) = runs_box(&live) {
+        let mut runs = live.iter().peekable();
+        for row in top..=bottom {
+            let mut col = left;
+            while let Some(&(_, run_col, length, _)) = runs.next_if(|&&(run_row, _, _, _)| run_row == row) {
+                text.push_str(&".".repeat(run_col - col));
+                text.push_str(&"O".repeat(length));
+                col = run_col + length;
+            }
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Encodes a `width x height` flat matrix as LifeWiki plaintext, cropped to the live
+/// cells. Dying states of Generations rules cannot be represented and are written dead.
+pub fn encode_plaintext(cells: &[u8], width: usize, height: usize, name: Option<&str>) -> String {
+    assert_eq!(cells.len(), width * height, "Expected {} cells for a {}x{} grid", width * height, width, height);
+    runs_to_plaintext(&matrix_runs(cells, width), name)
+}
+
+/// Encodes the live cells among row-major runs as Life 1.06, see `encode_life106`
+fn runs_to_life106(runs: &[(usize, usize, usize, u8)]) -> String {
+    let mut text = "#Life 1.06\n".to_string();
+    for &(row, col, length, _) in runs.iter().filter(|&&(_, _, _, state)| state == 1) {
+        for col in col..col + length {
+            text.push_str(&format!("{} {}\n", col, row));
+        }
+    }
+    text
+}
+
+/// Encodes the live cells of a `width x height` flat matrix as Life 1.06, with `x` the
+/// column and `y` the row of each cell on the grid
+pub fn encode_life106(cells: &[u8], width: usize) -> String {
+    runs_to_life106(&matrix_runs(cells, width))
+}
+
+/// Encodes the current generation of any engine in the given format. Like `universe_to_rle`
+/// this works from the live cells, so it copes with grids of any size.
+pub fn encode_universe(universe: &dyn LifeUniverse, format: PatternFormat) -> String {
+    match format {
+        PatternFormat::Rle => universe_to_rle(universe),
+        PatternFormat::Plaintext => runs_to_plaintext(&universe_runs(universe), None),
+        PatternFormat::Life106 | PatternFormat::Life105 => runs_to_life106(&universe_runs(universe)),
+    }
+}
+
+/// Saves the current generation of any engine, in the format implied by the extension
+/// of `file_path` (RLE for unknown extensions)
+pub fn write_pattern(universe: &dyn LifeUniverse, file_path: &str) -> io::Result<()> {
+    let format = PatternFormat::from_extension(file_path).unwrap_or(PatternFormat::Rle);
+    fs::write(file_path, encode_universe(universe, format))
+}
 
-    output_mat
+/// Where a pattern goes inside a larger universe
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Placement {
+    /// In the middle, rounding the offsets up
+    #[default]
+    Centered,
+    /// With the pattern's top-left cell at `(row, col)`
+    At { row: usize, col: usize },
 }
 
-pub fn calc_padding(big_n: usize, grid_size: usize) -> usize{
+/// Copies a `pattern_width x pattern_height` pattern into an empty `width x height` grid
+pub fn place_pattern(
+    cells: &[u8],
+    (pattern_width, pattern_height): (usize, usize),
+    (width, height): (usize, usize),
+    placement: Placement,
+) -> Result<Vec<u8>, RleError> {
+    let (row_offset, col_offset) = placement_offset((pattern_width, pattern_height), (width, height), placement)?;
+    let mut output_mat = vec![0; dense_len(width, height)?];
 
-    assert!(big_n>grid_size, "Pattern {}sq is too big for grid of size {}", grid_size, big_n);
+    // Copy the pattern row by row to its place in the grid
+    for (i, pattern_row) in cells.chunks(pattern_width.max(1)).enumerate().take(pattern_height) {
+        let target_idx = (i + row_offset) * width + col_offset;
+        output_mat[target_idx..target_idx + pattern_row.len()].copy_from_slice(pattern_row);
+    }
 
-    let diff = big_n-grid_size;
+    Ok(output_mat)
+}
 
-    match diff%2 {
-        0 => diff/2,
-        1 => (diff+1)/2,
-        _ => panic!("INTEGER DIVISION BY 2 YIELDED SMTH WEIRDD!!!!")
+/// `(row, col)` of the top-left cell of a `pattern_width x pattern_height` pattern put at
+/// `placement` in a `width x height` grid, or an error if it does not fit
+pub fn placement_offset(
+    (pattern_width, pattern_height): (usize, usize),
+    (width, height): (usize, usize),
+    placement: Placement,
+) -> Result<(usize, usize), RleError> {
+    let too_large = |offset| RleError::PatternTooLarge {
+        pattern: (pattern_width, pattern_height),
+        grid: (width, height),
+        offset,
+    };
+
+    match placement {
+        Placement::Centered => {
+            if pattern_width > width || pattern_height > height {
+                return Err(too_large(None));
+            }
+            Ok((calc_padding(height, pattern_height), calc_padding(width, pattern_width)))
+        }
+        Placement::At { row, col } => {
+            if row.checked_add(pattern_height).is_none_or(|bottom| bottom > height)
+                || col.checked_add(pattern_width).is_none_or(|right| right > width)
+            {
+                return Err(too_large(Some((row, col))));
+            }
+            Ok((row, col))
+        }
     }
 }
 
+/// Reads a pattern file into a flat `width x height` matrix, with the pattern at `placement`
+pub fn init_from_file(file_path: &str, width: usize, height: usize, placement: Placement) -> Result<Vec<u8>, RleError> {
+    let pattern = read_pattern(file_path)?;
+    pattern.place((width, height), placement)
+}
+
+/// Offset centering `grid_size` cells in `big_n`, rounded up. Panics if they do not fit.
+pub fn calc_padding(big_n: usize, grid_size: usize) -> usize {
+    assert!(big_n >= grid_size, "Pattern of size {} is too big for grid of size {}", grid_size, big_n);
+    (big_n - grid_size).div_ceil(2)
+}
+
 pub fn vec_to_matrix<T: Clone>(vec: &[T], n: usize) -> Vec<Vec<T>> {
     vec.chunks(n)
         .map(|chunk| chunk.to_vec())
@@ -196,50 +1025,280 @@ pub fn display(mat: &Vec<Vec<u8>>)
         }
     }
 
-pub fn get_memory_usage() -> u64 {
-    let mut sys = System::new_all();
-    sys.refresh_memory();
-    sys.used_memory() // Returns memory usage in KB
-}
     
+/// Creates the directory `file_path` goes into, if it has one
+fn create_parent_dir(file_path: &str) -> io::Result<()> {
+    match std::path::Path::new(file_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::create_dir_all(dir),
+        _ => Ok(()),
+    }
+}
+
+/// Version of the layout of results files, written in every record. Bump it whenever a
+/// column or metric is renamed or changes meaning.
+pub const RESULTS_SCHEMA_VERSION: u32 = 1;
+
+/// Run settings and machine, written alongside the results
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunInfo {
+    pub pattern: String,      // The pattern's #N name (and #O author), else the input file name
+    pub rule: String,         // Rule in B/S notation
+    pub topology: String,
+    pub iterations: usize,    // Generations per repetition
+    pub warmup: usize,        // Discarded runs before the measured ones
+    pub repetitions: usize,   // Measured runs
+    pub threads: usize,       // Threads available to the parallel engines
+    pub cpu_model: String,
+    pub git_revision: String, // Revision of the benchmarked code, "unknown" outside a git checkout
+    pub timestamp: String,    // When the run started, RFC 3339 in UTC
+}
+
+/// Columns of a results file, in order. Each record is one observation: the `value` of
+/// `metric` for `engine` on a `width x height` grid, in the `repetition`th measured run and
+/// at its `generation`th generation, both counted from 1. Figures of a whole run have no
+/// generation; statistics over all runs have neither.
+///
+/// Metrics: `time_ns` per generation; `total_ns`, `rss_kb`, `peak_rss_kb` and, when the
+/// allocations were counted, `heap_allocated_bytes`, `peak_heap_bytes` and `allocations`
+/// per run; `mean_ns`, `median_ns`, `stddev_ns`, `min_ns`, `max_ns`, `p95_ns`,
+/// `ci95_low_ns` and `ci95_high_ns` of the run totals.
+pub const RESULTS_COLUMNS: [&str; 18] = [
+    "schema_version", "timestamp", "git_revision", "cpu_model", "threads", "pattern", "rule", "topology",
+    "engine", "width", "height", "generations", "warmup", "repetitions", "repetition", "generation", "metric", "value",
+];
+
+const SUMMARY_KEYS: [&str; 8] = ["mean_ns", "median_ns", "stddev_ns", "min_ns", "max_ns", "p95_ns", "ci95_low_ns", "ci95_high_ns"];
+
+/// One record of a results file, its fields in the order of `RESULTS_COLUMNS`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Record {
+    schema_version: u32,
+    timestamp: String,
+    git_revision: String,
+    cpu_model: String,
+    threads: usize,
+    pattern: String,
+    rule: String,
+    topology: String,
+    engine: String,
+    width: usize,
+    height: usize,
+    generations: usize,
+    warmup: usize,
+    repetitions: usize,
+    repetition: Option<usize>,
+    generation: Option<usize>,
+    metric: String,
+    value: i64, // Times, memory and counts are whole numbers; statistics are rounded to the nanosecond
+}
+
+/// Every record of one engine on one grid size
+fn records(info: &RunInfo, result: &EngineResult) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut observe = |repetition, generation, metric: &str, value: f64| {
+        records.push(Record {
+            schema_version: RESULTS_SCHEMA_VERSION,
+            timestamp: info.timestamp.clone(),
+            git_revision: info.git_revision.clone(),
+            cpu_model: info.cpu_model.clone(),
+            threads: info.threads,
+            pattern: info.pattern.clone(),
+            rule: info.rule.clone(),
+            topology: info.topology.clone(),
+            engine: result.engine.clone(),
+            width: result.width,
+            height: result.height,
+            generations: info.iterations,
+            warmup: info.warmup,
+            repetitions: info.repetitions,
+            repetition,
+            generation,
+            metric: metric.to_string(),
+            value: value.round() as i64,
+        })
+    };
+
+    for (repetition, times) in result.generation_ns.iter().enumerate() {
+        for (generation, &time) in times.iter().enumerate() {
+            observe(Some(repetition + 1), Some(generation + 1), "time_ns", time as f64);
+        }
+        observe(Some(repetition + 1), None, "total_ns", times.iter().sum::<u64>() as f64);
+    }
+    for (repetition, memory) in result.memory.iter().enumerate() {
+        observe(Some(repetition + 1), None, "rss_kb", memory.rss_kb as f64);
+        observe(Some(repetition + 1), None, "peak_rss_kb", memory.peak_rss_kb as f64);
+        if let Some(heap) = memory.heap {
+            observe(Some(repetition + 1), None, "heap_allocated_bytes", heap.allocated_bytes as f64);
+            observe(Some(repetition + 1), None, "peak_heap_bytes", heap.peak_bytes as f64);
+            observe(Some(repetition + 1), None, "allocations", heap.allocations as f64);
+        }
+    }
+    if !result.generation_ns.is_empty() {
+        let summary = result.summary();
+        let statistics = [summary.mean, summary.median, summary.stddev, summary.min, summary.max, summary.p95, summary.ci_low, summary.ci_high];
+        for (metric, value) in SUMMARY_KEYS.iter().zip(statistics) {
+            observe(None, None, metric, value);
+        }
+    }
+    records
+}
+
+/// Writes the results as CSV with a header row, one observation per row
 pub fn write_results_to_csv(
-    all_results: &Vec<Vec<(usize, String, u128, Vec<u128>, Vec<u64>)>>, 
-    filename: &str,  
-    iterations: usize, 
-    file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    
-    let dir_name = "results_csv";
-    fs::create_dir_all(dir_name)?;
-    let file_path = format!("{}/{}", dir_name, filename);
-    let mut wtr = Writer::from_path(file_path)?;
-    
-    // Write metadata as the first row
-    wtr.write_record([
-        &format!("File Name: {}", file_name),  // This is the file name of the input file (e.g., "justyna.rle") 
-        //&format!(" Width: {}", grid_size.0),  // Width
-        //&format!(" Height: {}", grid_size.1),  // Height
-        &format!(" No. Iterations: {}", iterations), 
-        "", "", "" ] // Iterations
-        )?;
-    // Write the headers
-    wtr.write_record(["Grid size", "Name", "Global Time (ms)", "Times per 10 Iterations", "Memory Usage before and after (MB)"])?;
-    
-    for results in all_results{
-        for version_result in results {
-            let grid_size = version_result.0;
-            let name = &version_result.1;
-            let global_time = version_result.2;
-            let iteration_times = format!("{:?}", version_result.3);  // Convert Vec to string
-            let memory_use = format!("{:?}", version_result.4);
+    all_results: &[EngineResult],
+    file_path: &str,
+    info: &RunInfo) -> Result<(), Box<dyn std::error::Error>> {
     
-            // Write each row in the CSV
-            wtr.write_record([&grid_size.to_string(), name, &global_time.to_string(), &iteration_times, &memory_use.to_string()])?;
+    create_parent_dir(file_path)?;
+    // The header is written even when there are no results
+    let mut wtr = WriterBuilder::new().has_headers(false).from_path(file_path)?;
+    wtr.write_record(RESULTS_COLUMNS)?;
+    for result in all_results {
+        for record in records(info, result) {
+            wtr.serialize(record)?;
         }
     }
-    
     wtr.flush()?;
     Ok(())
 }
-        
-    
 
+/// Writes the same records as `write_results_to_csv` as JSON lines: one object per
+/// observation, keyed by column, with `null` for empty fields
+pub fn write_results_to_json(
+    all_results: &[EngineResult],
+    file_path: &str,
+    info: &RunInfo,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut json = String::new();
+    for result in all_results {
+        for record in records(info, result) {
+            json.push_str(&serde_json::to_string(&record)?);
+            json.push('\n');
+        }
+    }
+
+    create_parent_dir(file_path)?;
+    fs::write(file_path, json)?;
+    Ok(())
+}
+
+/// A results file read back
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResultsFile {
+    pub info: RunInfo,
+    pub results: Vec<EngineResult>, // Statistics are recomputed from the samples
+}
+
+/// The `index`th element of `items`, counted from 1 and at most `limit`, growing `items` as needed
+fn nth_mut<'a, T: Default>(items: &'a mut Vec<T>, index: usize, limit: usize, what: &str) -> Result<&'a mut T, String> {
+    if index == 0 || index > limit {
+        return Err(format!("{} {} is out of range, expected 1 to {}", what, index, limit));
+    }
+    if items.len() < index {
+        items.resize_with(index, T::default);
+    }
+    Ok(&mut items[index - 1])
+}
+
+/// Builds the results back from their records
+fn assemble_results(records: Vec<Record>) -> Result<ResultsFile, String> {
+    let mut info = None;
+    let mut results: Vec<EngineResult> = Vec::new();
+    // Every repetition and generation has records of its own, which bounds what a file can ask for
+    let count = records.len();
+
+    for (index, record) in records.into_iter().enumerate() {
+        let info = info.get_or_insert_with(|| RunInfo {
+            pattern: record.pattern.clone(),
+            rule: record.rule.clone(),
+            topology: record.topology.clone(),
+            iterations: record.generations,
+            warmup: record.warmup,
+            repetitions: record.repetitions,
+            threads: record.threads,
+            cpu_model: record.cpu_model.clone(),
+            git_revision: record.git_revision.clone(),
+            timestamp: record.timestamp.clone(),
+        });
+        let (repetitions, generations) = (info.repetitions.min(count), info.iterations.min(count));
+
+        let position = results
+            .iter()
+            .position(|result| result.engine == record.engine && result.width == record.width && result.height == record.height);
+        let result = match position {
+            Some(position) => &mut results[position],
+            None => {
+                results.push(EngineResult {
+                    engine: record.engine.clone(),
+                    width: record.width,
+                    height: record.height,
+                    generation_ns: Vec::new(),
+                    memory: Vec::new(),
+                });
+                results.last_mut().unwrap()
+            }
+        };
+
+        let in_record = |e: String| format!("record {}: {}", index + 1, e);
+        let value = u64::try_from(record.value).map_err(|_| in_record(format!("negative {} {}", record.metric, record.value)));
+        match (record.metric.as_str(), record.repetition, record.generation) {
+            ("time_ns", Some(repetition), Some(generation)) => {
+                let times = nth_mut(&mut result.generation_ns, repetition, repetitions, "repetition").map_err(in_record)?;
+                *nth_mut(times, generation, generations, "generation").map_err(in_record)? = value?;
+            }
+            (metric @ ("rss_kb" | "peak_rss_kb" | "heap_allocated_bytes" | "peak_heap_bytes" | "allocations"), Some(repetition), None) => {
+                let memory = nth_mut(&mut result.memory, repetition, repetitions, "repetition").map_err(in_record)?;
+                let value = value?;
+                match metric {
+                    "rss_kb" => memory.rss_kb = value,
+                    "peak_rss_kb" => memory.peak_rss_kb = value,
+                    "heap_allocated_bytes" => memory.heap.get_or_insert_with(HeapStats::default).allocated_bytes = value,
+                    "peak_heap_bytes" => memory.heap.get_or_insert_with(HeapStats::default).peak_bytes = value,
+                    _ => memory.heap.get_or_insert_with(HeapStats::default).allocations = value,
+                }
+            }
+            // Run totals and statistics follow from the samples, and newer metrics are skipped
+            _ => {}
+        }
+    }
+
+    let info = info.ok_or("no results")?;
+    if let Some(result) = results.iter().find(|result| result.generation_ns.is_empty()) {
+        return Err(format!("no times for {} on the {} grid", result.engine, result.size()));
+    }
+    Ok(ResultsFile { info, results })
+}
+
+/// Checks the schema version of the `index`th record before its fields are read
+fn check_version(index: usize, version: Option<u64>) -> Result<(), String> {
+    match version {
+        Some(version) if version == RESULTS_SCHEMA_VERSION as u64 => Ok(()),
+        Some(version) => {
+            Err(format!("record {}: schema version {} is not supported, expected {}", index + 1, version, RESULTS_SCHEMA_VERSION))
+        }
+        None => Err(format!("record {}: missing or invalid schema_version", index + 1)),
+    }
+}
+
+/// Reads a results file written by `write_results_to_csv` or `write_results_to_json`
+pub fn read_results(file_path: &str) -> Result<ResultsFile, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(file_path)?;
+    let mut records = Vec::new();
+    if text.trim_start().starts_with('{') {
+        for (index, line) in text.lines().filter(|line| !line.trim().is_empty()).enumerate() {
+            let value: serde_json::Value = serde_json::from_str(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+            check_version(index, value.get("schema_version").and_then(serde_json::Value::as_u64))?;
+            records.push(Record::deserialize(value).map_err(|e| format!("line {}: {}", index + 1, e))?);
+        }
+    } else {
+        let mut reader = ReaderBuilder::new().from_reader(text.as_bytes());
+        let header = reader.headers()?.clone();
+        let version_column = header.iter().position(|column| column == "schema_version");
+        for (index, record) in reader.records().enumerate() {
+            let record = record?;
+            check_version(index, version_column.and_then(|column| record.get(column)?.parse().ok()))?;
+            records.push(record.deserialize(Some(&header))?);
+        }
+    }
+    Ok(assemble_results(records)?)
+}
@@ -0,0 +1,93 @@
+//! Differential checking: runs several engines side by side and reports where they first disagree
+use std::collections::BTreeSet;
+use std::fmt;
+use crate::traits::LifeUniverse;
+
+/// Most differing cells listed in a `Divergence`
+const MAX_REPORTED_CELLS: usize = 20;
+
+/// First generation at which an engine's live cells differ from the reference engine's
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub generation: usize,                 // Generation the difference was found at
+    pub reference: String,                 // Name of the first engine, the one all others are compared with
+    pub engine: String,                    // Name of the engine that differs
+    pub differing_cells: usize,            // Number of cells that differ
+    pub only_in_reference: Vec<(usize, usize)>, // Alive in the reference but not in the engine, at most 20
+    pub only_in_engine: Vec<(usize, usize)>,    // Alive in the engine but not in the reference, at most 20
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "generation {}: {} differs from {} at {} cells",
+            self.generation, self.engine, self.reference, self.differing_cells
+        )?;
+        let cells = |cells: &[(usize, usize)]| {
+            cells.iter().map(|(row, col)| format!("({}, {})", row, col)).collect::<Vec<_>>().join(" ")
+        };
+        if !self.only_in_reference.is_empty() {
+            write!(f, "\n  alive only in {}: {}", self.reference, cells(&self.only_in_reference))?;
+        }
+        if !self.only_in_engine.is_empty() {
+            write!(f, "\n  alive only in {}: {}", self.engine, cells(&self.only_in_engine))?;
+        }
+        if self.differing_cells > self.only_in_reference.len() + self.only_in_engine.len() {
+            write!(f, "\n  ...")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Live cells of a universe in row-major order
+fn live_set(universe: &dyn LifeUniverse) -> BTreeSet<(usize, usize)> {
+    universe.live_cells().collect()
+}
+
+/// Compares every engine with the first one, returning the first that differs
+fn compare_at(engines: &[(&str, Box<dyn LifeUniverse>)], generation: usize) -> Result<(), Divergence> {
+    let Some(((reference_name, reference), others)) = engines.split_first() else {
+        return Ok(());
+    };
+    let expected = live_set(reference.as_ref());
+
+    for (name, engine) in others {
+        let actual = live_set(engine.as_ref());
+        if actual != expected {
+            let only_in_reference: Vec<_> = expected.difference(&actual).copied().collect();
+            let only_in_engine: Vec<_> = actual.difference(&expected).copied().collect();
+            return Err(Divergence {
+                generation,
+                reference: reference_name.to_string(),
+                engine: name.to_string(),
+                differing_cells: only_in_reference.len() + only_in_engine.len(),
+                only_in_reference: only_in_reference.into_iter().take(MAX_REPORTED_CELLS).collect(),
+                only_in_engine: only_in_engine.into_iter().take(MAX_REPORTED_CELLS).collect(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Runs all `engines`, which must start from the same state, for `generations` generations,
+/// comparing their live cells with those of the first engine initially and then every
+/// `every` generations (and at the end). With `every > 1` the engines first diverged at
+/// most `every - 1` generations before the reported one.
+pub fn compare_engines(engines: &mut [(&str, Box<dyn LifeUniverse>)], generations: usize, every: usize) -> Result<(), Divergence> {
+    assert!(every > 0, "Engines must be compared at least every generation");
+    compare_at(engines, 0)?;
+
+    let mut generation = 0;
+    while generation < generations {
+        let step = every.min(generations - generation);
+        for (_, engine) in engines.iter_mut() {
+            engine.step(step);
+        }
+        generation += step;
+        compare_at(engines, generation)?;
+    }
+    Ok(())
+}
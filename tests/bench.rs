@@ -0,0 +1,209 @@
+//! Summary statistics of benchmark samples
+use wasm_game_of_life::bench::{time_generations, Comparison, EngineResult, Summary};
+use wasm_game_of_life::traits::LifeUniverse;
+use wasm_game_of_life::memory::{HeapStats, MemoryUse};
+use wasm_game_of_life::utils::{read_results, write_results_to_csv, write_results_to_json, RunInfo};
+
+fn close(actual: f64, expected: f64) -> bool {
+    (actual - expected).abs() < 1e-3
+}
+
+#[test]
+fn summary_of_known_samples() {
+    let summary = Summary::of(&[2, 4, 4, 4, 5, 5, 7, 9]);
+    assert_eq!(summary.samples, 8);
+    assert!(close(summary.mean, 5.0));
+    assert!(close(summary.median, 4.5));
+    assert!(close(summary.stddev, (32.0f64 / 7.0).sqrt()));
+    assert!(close(summary.min, 2.0));
+    assert!(close(summary.max, 9.0));
+    assert!(close(summary.p95, 8.3));
+    // t(7) = 2.365
+    let margin = 2.365 * (32.0f64 / 7.0).sqrt() / 8.0f64.sqrt();
+    assert!(close(summary.ci_low, 5.0 - margin) && close(summary.ci_high, 5.0 + margin));
+}
+
+#[test]
+fn summary_of_one_sample() {
+    let summary = Summary::of(&[42]);
+    assert_eq!((summary.mean, summary.median, summary.stddev, summary.p95), (42.0, 42.0, 0.0, 42.0));
+    assert_eq!((summary.ci_low, summary.ci_high), (42.0, 42.0));
+}
+
+#[test]
+fn engine_results_are_summarized_over_repetitions() {
+    let result = EngineResult {
+        engine: "Naive".to_string(),
+        width: 64,
+        height: 64,
+        generation_ns: vec![vec![1, 2, 3], vec![3, 4, 5], vec![2, 9, 1]],
+        memory: vec![],
+    };
+    assert_eq!(result.totals(), vec![6, 12, 12]);
+    assert!(close(result.summary().mean, 10.0));
+    assert_eq!(result.generation_medians(), vec![2, 4, 3]);
+}
+
+#[test]
+fn every_generation_is_timed() {
+    let mut universe = wasm_game_of_life::Universe::from_matrix(8, 8, vec![0; 64]);
+    assert_eq!(time_generations(&mut universe, 7).len(), 7);
+}
+
+fn run_info() -> RunInfo {
+    RunInfo {
+        pattern: "Blom".to_string(),
+        rule: "B3/S23".to_string(),
+        topology: "plane".to_string(),
+        iterations: 3,
+        warmup: 1,
+        repetitions: 2,
+        threads: 4,
+        cpu_model: "Some \"quoted\", CPU \u{1F680}".to_string(),
+        git_revision: "0123456789ab-dirty".to_string(),
+        timestamp: "2024-05-06T07:08:09Z".to_string(),
+    }
+}
+
+fn sample_results() -> Vec<EngineResult> {
+    vec![
+        EngineResult {
+            engine: "Hashlife".to_string(),
+            width: 640,
+            height: 480,
+            generation_ns: vec![vec![10, 20, 30], vec![20, 30, 40]],
+            memory: vec![MemoryUse { rss_kb: 100, peak_rss_kb: 120, heap: None }; 2],
+        },
+        EngineResult {
+            engine: "Sparse".to_string(),
+            width: 64,
+            height: 64,
+            generation_ns: vec![vec![5, 6, 7], vec![8, 9, 10]],
+            memory: vec![
+                MemoryUse { rss_kb: 90, peak_rss_kb: 95, heap: Some(HeapStats { allocated_bytes: 4096, peak_bytes: 2048, allocations: 12 }) },
+                MemoryUse { rss_kb: 91, peak_rss_kb: 96, heap: Some(HeapStats { allocated_bytes: 4000, peak_bytes: 2000, allocations: 11 }) },
+            ],
+        },
+    ]
+}
+
+#[test]
+fn results_files_are_read_back() {
+    let (results, info) = (sample_results(), run_info());
+    let path = std::env::temp_dir().join(format!("read_back_{}.csv", std::process::id()));
+    let path = path.to_str().unwrap();
+    write_results_to_csv(&results, path, &info).unwrap();
+    let text = std::fs::read_to_string(path).unwrap();
+    let read = read_results(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert!(text.starts_with("schema_version,timestamp,git_revision,cpu_model,threads,pattern,"));
+    assert!(text.contains(",Hashlife,640,480,3,1,2,2,3,time_ns,40\n"));
+    assert!(text.contains(",Sparse,64,64,3,1,2,,,mean_ns,"));
+    assert_eq!(read.info, info);
+    assert_eq!(read.results, results);
+}
+
+#[test]
+fn json_lines_are_read_back() {
+    let (results, info) = (sample_results(), run_info());
+    let path = std::env::temp_dir().join(format!("read_back_{}.jsonl", std::process::id()));
+    let path = path.to_str().unwrap();
+    write_results_to_json(&results, path, &info).unwrap();
+    let text = std::fs::read_to_string(path).unwrap();
+    let read = read_results(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    let first = text.lines().next().unwrap();
+    assert!(first.starts_with("{\"schema_version\":1,\"timestamp\":\"2024-05-06T07:08:09Z\""), "{}", first);
+    assert!(first.ends_with("\"repetition\":1,\"generation\":1,\"metric\":\"time_ns\",\"value\":10}"), "{}", first);
+    assert_eq!(read.info, info);
+    assert_eq!(read.results, results);
+}
+
+#[test]
+fn results_of_another_schema_version_are_rejected() {
+    let path = std::env::temp_dir().join(format!("future_{}.csv", std::process::id()));
+    let path = path.to_str().unwrap();
+    write_results_to_csv(&sample_results(), path, &run_info()).unwrap();
+    let text = std::fs::read_to_string(path).unwrap().replace("\n1,", "\n99,");
+    std::fs::write(path, text).unwrap();
+    let read = read_results(path);
+    std::fs::remove_file(path).unwrap();
+    assert!(read.is_err());
+}
+
+#[test]
+fn welch_test_of_known_samples() {
+    let comparison = Comparison::of(&[10, 11, 12, 13, 14], &[14, 15, 16, 17, 18]);
+    assert!(close(comparison.speedup, 12.0 / 16.0));
+    assert!(close(comparison.change, 1.0 / 3.0));
+    // Equal variances of 2.5: t = 4 / sqrt(2.5 / 5 * 2), and 8 degrees of freedom
+    assert!(close(comparison.t, 4.0));
+    assert!(close(comparison.dof, 8.0));
+    assert!(comparison.significant);
+}
+
+#[test]
+fn overlapping_samples_are_not_significant() {
+    let comparison = Comparison::of(&[10, 20, 30], &[12, 22, 29]);
+    assert!(comparison.t > 0.0 && !comparison.significant);
+}
+
+#[test]
+fn single_samples_are_never_significant() {
+    let comparison = Comparison::of(&[10], &[1000, 1001]);
+    assert!(close(comparison.speedup, 10.0 / 1000.5));
+    assert!(!comparison.significant);
+}
+
+#[test]
+fn constant_samples_differ_when_their_means_do() {
+    assert!(Comparison::of(&[5, 5, 5], &[6, 6, 6]).significant);
+    assert!(!Comparison::of(&[5, 5, 5], &[5, 5, 5]).significant);
+}
+
+/// A results file holding `records`, one JSON object per line, read back
+fn read_json_lines(name: &str, records: &[String]) -> Result<wasm_game_of_life::utils::ResultsFile, String> {
+    let path = std::env::temp_dir().join(format!("{}_{}.jsonl", name, std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, records.join("\n")).unwrap();
+    let read = read_results(path).map_err(|e| e.to_string());
+    std::fs::remove_file(path).unwrap();
+    read
+}
+
+fn json_record(cpu_model: &str, repetition: usize, generation: usize) -> String {
+    format!(
+        "{{\"schema_version\":1,\"timestamp\":\"2024-05-06T07:08:09Z\",\"git_revision\":\"abc\",\"cpu_model\":\"{}\",\
+         \"threads\":1,\"pattern\":\"Blom\",\"rule\":\"B3/S23\",\"topology\":\"plane\",\"engine\":\"Naive\",\"width\":8,\
+         \"height\":8,\"generations\":2,\"warmup\":0,\"repetitions\":1,\"repetition\":{},\"generation\":{},\
+         \"metric\":\"time_ns\",\"value\":5}}",
+        cpu_model, repetition, generation
+    )
+}
+
+#[test]
+fn json_escapes_are_decoded() {
+    let read = read_json_lines("escapes", &[json_record("\\ud83d\\ude80 \\u00e9\\\"", 1, 1), json_record("", 1, 2)]).unwrap();
+    assert_eq!(read.info.cpu_model, "\u{1F680} \u{e9}\"");
+    assert_eq!(read.results[0].generation_ns, vec![vec![5, 5]]);
+}
+
+#[test]
+fn indices_beyond_the_run_are_rejected() {
+    let error = read_json_lines("generation", &[json_record("", 1, 3)]).unwrap_err();
+    assert!(error.contains("generation 3 is out of range"), "{}", error);
+    let error = read_json_lines("repetition", &[json_record("", 1000000000000, 1)]).unwrap_err();
+    assert!(error.contains("repetition 1000000000000 is out of range"), "{}", error);
+    let error = read_json_lines("zero", &[json_record("", 0, 1)]).unwrap_err();
+    assert!(error.contains("repetition 0 is out of range"), "{}", error);
+}
+
+#[test]
+fn malformed_numbers_are_rejected() {
+    let record = json_record("", 1, 1).replace("\"value\":5", "\"value\":05");
+    assert!(read_json_lines("leading_zero", &[record]).is_err());
+    let record = json_record("", 1, 1).replace("\"width\":8", "\"width\":8x");
+    assert!(read_json_lines("trailing", &[record]).is_err());
+}
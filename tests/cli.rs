@@ -0,0 +1,93 @@
+//! Command line parsing of the `performance_test` binary and its exit codes
+use std::process::{Command, Output};
+use wasm_game_of_life::utils::read_results;
+
+fn performance_test(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_performance_test")).args(args).output().unwrap()
+}
+
+/// Runs with `args`, checking that they are rejected as a usage error, and returns the message
+fn usage_error(args: &[&str]) -> String {
+    let output = performance_test(args);
+    assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    assert!(output.stdout.is_empty(), "{:?}", args);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn help_is_printed_on_stdout() {
+    for (args, first_line) in [
+        (&["--help"][..], "Times the Game of Life engines on one or more patterns."),
+        (&["-h", "--bogus"][..], "Times the Game of Life engines on one or more patterns."),
+        (&["report", "--help"][..], "Draws charts of results files (CSV or JSON lines): mean total time against grid size, time per 10 generations"),
+        (&["compare", "-h"][..], "Compares two results files (CSV or JSON lines) engine by engine and grid size by grid size: the speedup of"),
+    ] {
+        let output = performance_test(args);
+        assert!(output.status.success(), "{:?}", args);
+        assert_eq!(String::from_utf8(output.stdout).unwrap().lines().next(), Some(first_line), "{:?}", args);
+    }
+}
+
+#[test]
+fn invalid_flags_are_usage_errors() {
+    for (args, message) in [
+        (&["--bogus"][..], "unknown option '--bogus'"),
+        (&["--scales", "11"][..], "invalid value '11' for --scales"),
+        (&["--scales=2,x"][..], "invalid value 'x' for --scales"),
+        (&["--sizes", "0x64"][..], "invalid value '0x64' for --sizes"),
+        (&["--scales", ""][..], "no grid sizes to run"),
+        (&["-i", "0"][..], "invalid value '0' for -i, expected a positive number"),
+        (&["--warmup", "-1"][..], "invalid value '-1' for --warmup, expected a number"),
+        (&["--repetitions"][..], "missing value for --repetitions"),
+        (&["--engines", "naive,warp"][..], "invalid value 'warp' for --engines"),
+        (&["--engines", "naive", "-x", "naive"][..], "no engines left to run"),
+        (&["--topology", "moebius"][..], "unknown topology 'moebius'"),
+        (&["--topology", "K64,64*"][..], "unsupported Golly grid 'K64,64*'"),
+        (&["--format", "xml"][..], "unknown output format 'xml', expected csv or jsonl"),
+        (&["--timeout", "5"][..], "--timeout needs --isolate"),
+        (&["a.rle", "b.rle", "-o", "out.csv"][..], "--output needs a {pattern} placeholder when running several patterns"),
+        (&["report", "--format", "gif", "a.csv"][..], "unknown chart format 'gif', expected png or svg"),
+        (&["compare", "a.csv", "b.csv", "-t", "-5"][..], "invalid value '-5' for -t, expected a percentage"),
+        (&["compare", "a.csv", "b.csv", "c.csv"][..], "compare needs a baseline and a candidate results file"),
+    ] {
+        let stderr = usage_error(args);
+        assert!(stderr.starts_with(&format!("error: {}", message)), "{:?}: {}", args, stderr);
+        assert!(stderr.contains("Run with --help for usage."), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn the_largest_scale_is_accepted() {
+    // Scale 10 is parsed, then the missing pattern fails the run rather than the parsing
+    let output = performance_test(&["--scales", "10", "no_such_pattern.rle"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("Cannot read no_such_pattern.rle"), "{}", stderr);
+}
+
+#[test]
+fn grids_too_large_for_a_flat_matrix_are_skipped() {
+    let output_path = std::env::temp_dir().join(format!("oversized_{}.csv", std::process::id()));
+    let output = performance_test(&[
+        "grids/blom.rle", "--sizes", "100000,32", "--engines", "hashlife", "-i", "2", "-w", "0", "-r", "1",
+        "-o", output_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Skipping 100000: 100000x100000 cells are too many for a flat matrix"), "{}", stdout);
+
+    let results = read_results(output_path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&output_path).unwrap();
+    let sizes: Vec<(usize, usize)> = results.results.iter().map(|result| (result.width, result.height)).collect();
+    assert_eq!(sizes, vec![(32, 32)]);
+}
+
+#[test]
+fn verifying_reports_agreement_and_fails_on_unreadable_patterns() {
+    let output = performance_test(&["grids/blom.rle", "--verify", "--sizes", "32", "-i", "8", "--engines", "naive,bitwise"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("OK: grids/blom.rle 32x32: all engines agree for 8 generations"));
+
+    let output = performance_test(&["no_such_pattern.rle", "--verify", "--sizes", "32"]);
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,32 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+use std::collections::BTreeSet;
+use wasm_game_of_life::traits::LifeUniverse;
+use wasm_game_of_life::utils::{parse_rle, Placement};
+use wasm_game_of_life::{bitwise, hashed_parallel, hashlife, optimized_alg, parallelize, sparse_matrix, track_alive_cells};
+
+/// Every engine, built from the same flat matrix, paired with its name. The naive one comes first.
+pub fn all_engines(width: usize, height: usize, flat_matrix: &[u8]) -> Vec<(&'static str, Box<dyn LifeUniverse>)> {
+    let m = || flat_matrix.to_vec();
+    vec![
+        ("Naive", Box::new(wasm_game_of_life::Universe::from_matrix(width, height, m()))),
+        ("Sparse", Box::new(sparse_matrix::Universe::from_matrix(width, height, m()))),
+        ("Optimized", Box::new(optimized_alg::Universe::from_matrix(width, height, m()))),
+        ("TrackAliveCells", Box::new(track_alive_cells::Universe::from_matrix(width, height, m()))),
+        ("Parallel", Box::new(parallelize::Universe::from_matrix(width, height, m()))),
+        ("HashParallel", Box::new(hashed_parallel::Universe::from_matrix(width, height, m()))),
+        ("Bitwise", Box::new(bitwise::Universe::from_matrix(width, height, m()))),
+        ("Hashlife", Box::new(hashlife::Universe::from_matrix(width, height, m()))),
+    ]
+}
+
+/// A `width x height` flat matrix holding the RLE pattern with its top-left cell at `(row, col)`
+pub fn grid_with(rle: &str, width: usize, height: usize, row: usize, col: usize) -> Vec<u8> {
+    let pattern = parse_rle(rle).unwrap();
+    pattern.place((width, height), Placement::At { row, col }).unwrap()
+}
+
+/// Live cells of a universe in row-major order
+pub fn live(universe: &dyn LifeUniverse) -> BTreeSet<(usize, usize)> {
+    universe.live_cells().collect()
+}
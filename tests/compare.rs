@@ -0,0 +1,88 @@
+//! Regression checks of `performance_test compare`
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use wasm_game_of_life::bench::EngineResult;
+use wasm_game_of_life::utils::{write_results_to_csv, write_results_to_json, RunInfo};
+
+/// Results of the naive and sparse engines on a 64 grid, taking `naive` and `sparse` ns per generation
+fn results(naive: [u64; 4], sparse: [u64; 4]) -> Vec<EngineResult> {
+    let result = |engine: &str, times: [u64; 4]| EngineResult {
+        engine: engine.to_string(),
+        width: 64,
+        height: 64,
+        generation_ns: times.iter().map(|&time| vec![time; 10]).collect(),
+        memory: vec![Default::default(); 4],
+    };
+    vec![result("Naive", naive), result("Sparse", sparse)]
+}
+
+fn info(pattern: &str) -> RunInfo {
+    RunInfo { pattern: pattern.to_string(), iterations: 10, repetitions: 4, ..Default::default() }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("compare_{}_{}", std::process::id(), name))
+}
+
+fn compare(baseline: &PathBuf, candidate: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_performance_test")).arg("compare").arg(baseline).arg(candidate).args(args).output().unwrap()
+}
+
+#[test]
+fn significant_slowdowns_beyond_the_threshold_fail() {
+    let (baseline, candidate) = (temp_path("slow_base.csv"), temp_path("slow_candidate.jsonl"));
+    write_results_to_csv(&results([100, 101, 99, 100], [50, 51, 49, 50]), baseline.to_str().unwrap(), &info("Blom")).unwrap();
+    // The naive engine 3% faster, the sparse one 20% slower
+    write_results_to_json(&results([97, 98, 96, 97], [60, 61, 59, 60]), candidate.to_str().unwrap(), &info("Blom")).unwrap();
+
+    let output = compare(&baseline, &candidate, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.lines().any(|line| line.starts_with("Naive") && line.ends_with("faster")), "{}", stdout);
+    assert!(stdout.lines().any(|line| line.starts_with("Sparse") && line.ends_with("REGRESSION")), "{}", stdout);
+
+    // Within a looser threshold the slowdown is reported but does not fail
+    let output = compare(&baseline, &candidate, &["--threshold", "25"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.lines().any(|line| line.starts_with("Sparse") && line.ends_with("slower")), "{}", stdout);
+
+    std::fs::remove_file(baseline).unwrap();
+    std::fs::remove_file(candidate).unwrap();
+}
+
+#[test]
+fn noise_is_not_a_regression() {
+    let (baseline, candidate) = (temp_path("noise_base.csv"), temp_path("noise_candidate.csv"));
+    write_results_to_csv(&results([100, 140, 60, 100], [50, 70, 30, 50]), baseline.to_str().unwrap(), &info("Blom")).unwrap();
+    write_results_to_csv(&results([110, 150, 70, 110], [55, 75, 35, 55]), candidate.to_str().unwrap(), &info("Blom")).unwrap();
+
+    let output = compare(&baseline, &candidate, &["-t", "1"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert_eq!(stdout.matches("no significant change").count(), 2, "{}", stdout);
+
+    std::fs::remove_file(baseline).unwrap();
+    std::fs::remove_file(candidate).unwrap();
+}
+
+#[test]
+fn results_of_different_patterns_do_not_match() {
+    let (baseline, candidate) = (temp_path("blom.csv"), temp_path("glider.csv"));
+    write_results_to_csv(&results([100; 4], [50; 4]), baseline.to_str().unwrap(), &info("Blom")).unwrap();
+    write_results_to_csv(&results([100; 4], [50; 4]), candidate.to_str().unwrap(), &info("Glider")).unwrap();
+
+    let output = compare(&baseline, &candidate, &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no engine, pattern and grid size in common"));
+
+    std::fs::remove_file(baseline).unwrap();
+    std::fs::remove_file(candidate).unwrap();
+}
+
+#[test]
+fn compare_needs_two_results_files() {
+    let output = Command::new(env!("CARGO_BIN_EXE_performance_test")).args(["compare", "one.csv"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,341 @@
+//! Reading and writing pattern files
+use wasm_game_of_life::rules::Rule;
+use wasm_game_of_life::hashlife::{MacrocellError, Universe};
+use wasm_game_of_life::topology::{Topology, TopologyError};
+use wasm_game_of_life::traits::LifeUniverse;
+use wasm_game_of_life::utils::{
+    encode_rle, encode_universe, parse_life105, parse_life106, parse_pattern, parse_plaintext, parse_rle,
+    universe_to_rle, PatternFormat, Placement, RleError, MAX_DENSE_CELLS,
+};
+
+#[test]
+fn rle_cells_are_decoded() {
+    let pattern = parse_rle("x = 4, y = 3\nb2o$3bo$o!").unwrap();
+    assert_eq!((pattern.width, pattern.height), (4, 3));
+    assert_eq!(pattern.cells().unwrap(), vec![0, 1, 1, 0, 0, 0, 0, 1, 1, 0, 0, 0]);
+}
+
+#[test]
+fn run_counts_too_large_are_rejected() {
+    let error = parse_rle("x = 3, y = 2\n3o$\nbo99999999999999999999999o!").unwrap_err();
+    assert!(matches!(error, RleError::RunCountTooLarge { line: 3, column: 3 }), "{:?}", error);
+    assert_eq!(error.to_string(), "line 3, column 3: run count is too large");
+}
+
+#[test]
+fn errors_point_at_the_offending_character() {
+    let error = parse_rle("#C comment\nx = 3, y = 2\nbo$\n o?!").unwrap_err();
+    assert!(matches!(error, RleError::UnexpectedChar { line: 4, column: 3, found: '?' }), "{:?}", error);
+
+    let error = parse_rle("x = 3, y = 1\n2o2o!").unwrap_err();
+    assert!(matches!(error, RleError::RowTooLong { line: 2, column: 4, length: 4, expected: 3 }), "{:?}", error);
+
+    let error = parse_rle("x = 2, y = 1\no$o!").unwrap_err();
+    assert!(matches!(error, RleError::TooManyRows { line: 2, column: 3, rows: 2, expected: 1 }), "{:?}", error);
+}
+
+#[test]
+fn multi_state_letters_are_decoded() {
+    let pattern = parse_rle("x = 4, y = 1, rule = 23/3/256\n.ApAyO!").unwrap();
+    assert_eq!(pattern.cells().unwrap(), vec![0, 1, 25, 255]);
+}
+
+#[test]
+fn states_beyond_the_rule_are_rejected() {
+    // yX would be state 264, past the largest state yO
+    let error = parse_rle("x = 2, y = 1, rule = 23/3/256\nAyX!").unwrap_err();
+    assert!(matches!(error, RleError::InvalidState { line: 2, column: 3, state: 264, states: 256 }), "{:?}", error);
+
+    let error = parse_rle("x = 3, y = 1\noAC!").unwrap_err();
+    assert!(matches!(error, RleError::InvalidState { line: 2, column: 3, state: 3, states: 2 }), "{:?}", error);
+    assert_eq!(error.to_string(), "line 2, column 3: state 3 does not exist in a rule with 2 states");
+
+    let error = parse_rle("x = 2, y = 1, rule = B2/S/3\n.C!").unwrap_err();
+    assert!(matches!(error, RleError::InvalidState { state: 3, states: 3, .. }), "{:?}", error);
+}
+
+#[test]
+fn rle_metadata_is_read_from_comment_lines() {
+    let text = "#N Gosper glider gun\n\
+                #O Bill Gosper\n\
+                #C The first known gun\n\
+                #c www.conwaylife.com/wiki/Gosper_glider_gun\n\
+                #R -18 -5\n\
+                #r 23/36\n\
+                #X unknown line\n\
+                x = 3, y = 1\n\
+                3o!";
+    let pattern = parse_rle(text).unwrap();
+    let metadata = &pattern.metadata;
+    assert_eq!(metadata.name.as_deref(), Some("Gosper glider gun"));
+    assert_eq!(metadata.label("gun.rle"), "Gosper glider gun");
+    assert_eq!(metadata.author.as_deref(), Some("Bill Gosper"));
+    assert_eq!(
+        metadata.comments,
+        vec!["The first known gun", "www.conwaylife.com/wiki/Gosper_glider_gun", "#X unknown line"]
+    );
+    assert_eq!(metadata.offset, Some((-18, -5)));
+    assert_eq!(metadata.rule, Rule::parse("B36/S23").unwrap());
+    assert_eq!(metadata.topology, None);
+    assert_eq!(pattern.live_cells(), vec![(0, 0), (0, 1), (0, 2)]);
+
+    // #P is the older spelling of #R, and the header rule wins over #r
+    let pattern = parse_rle("#P 3 4\n#r B36/S23\nx = 1, y = 1, rule = B3/S23:P8,8\no!").unwrap();
+    assert_eq!(pattern.metadata.offset, Some((3, 4)));
+    assert_eq!(pattern.metadata.rule, Rule::conway());
+    assert_eq!(pattern.metadata.topology, Some(Topology::Plane));
+    assert_eq!(pattern.metadata.name, None);
+    assert_eq!(pattern.metadata.label("fallback"), "fallback");
+
+    let error = parse_rle("#N ok\n#R 1\nx = 1, y = 1\no!").unwrap_err();
+    assert!(matches!(error, RleError::InvalidMetadata { line: 2, column: 1, .. }), "{:?}", error);
+    let error = parse_rle("#r B9/S\nx = 1, y = 1\no!").unwrap_err();
+    assert!(matches!(error, RleError::InvalidRule { line: 1, column: 4, .. }), "{:?}", error);
+}
+
+#[test]
+fn huge_headers_cost_no_memory() {
+    let pattern = parse_rle("x = 100000000000, y = 100000000000\n2o$99999999999bo!").unwrap();
+    assert_eq!(pattern.live_cells(), vec![(0, 0), (0, 1), (1, 99999999999)]);
+    assert!(matches!(pattern.cells(), Err(RleError::TooManyCells { width: 100000000000, height: 100000000000 })));
+    assert!(matches!(pattern.place((64, 64), Placement::Centered), Err(RleError::PatternTooLarge { .. })));
+
+    let pattern = parse_rle("x = 18446744073709551615, y = 2\no!").unwrap();
+    assert!(matches!(pattern.cells(), Err(RleError::TooManyCells { .. })));
+    assert!(matches!(pattern.place((4, 4), Placement::At { row: 3, col: 0 }), Err(RleError::PatternTooLarge { .. })));
+}
+
+#[test]
+fn sparse_life106_patterns_keep_their_cells_only() {
+    let pattern = parse_life106("#Life 1.06\n0 0\n1000000 1000000\n0 0\n").unwrap();
+    assert_eq!((pattern.width, pattern.height), (1000001, 1000001));
+    assert_eq!(pattern.live_cells(), vec![(0, 0), (1000000, 1000000)]);
+    assert!(matches!(pattern.cells(), Err(RleError::TooManyCells { .. })));
+
+    let error = parse_life106("#Life 1.06\n-9223372036854775808 0\n9223372036854775807 0\n").unwrap_err();
+    assert!(matches!(error, RleError::InvalidCoordinates { line: 3, column: 1, .. }), "{:?}", error);
+}
+
+#[test]
+fn encoded_rle_is_cropped_to_the_pattern() {
+    let mut cells = vec![0; 6 * 5];
+    for &(row, col) in &[(1, 2), (1, 3), (3, 3)] {
+        cells[row * 6 + col] = 1;
+    }
+    let text = encode_rle(&cells, 6, 5, &Rule::conway(), Topology::Unbounded);
+    assert_eq!(text, "x = 2, y = 3, rule = B3/S23\n2o2$bo!\n");
+
+    let empty = encode_rle(&[0; 4], 2, 2, &Rule::conway(), Topology::Unbounded);
+    assert_eq!(empty, "x = 0, y = 0, rule = B3/S23\n!\n");
+}
+
+#[test]
+fn encoded_rle_header_carries_the_rule_and_grid() {
+    let rule = Rule::parse("B2/S/C3").unwrap();
+    let text = encode_rle(&[0, 1, 2, 0], 4, 1, &rule, Topology::Torus);
+    assert_eq!(text, "x = 2, y = 1, rule = B2/S/C3:T4,1\nAB!\n");
+
+    let text = encode_rle(&[1, 0, 0, 1], 2, 2, &Rule::parse("B36/S23").unwrap(), Topology::KleinBottle);
+    assert_eq!(text, "x = 2, y = 2, rule = B36/S23:K2*,2\no$bo!\n");
+
+    let pattern = parse_rle(&text).unwrap();
+    assert_eq!(pattern.metadata.rule, Rule::parse("B36/S23").unwrap());
+    assert_eq!(pattern.metadata.topology, Some(Topology::KleinBottle));
+}
+
+#[test]
+fn universes_larger_than_a_flat_matrix_are_encoded_from_their_live_cells() {
+    let side = 1 << 20;
+    assert!(side * side > MAX_DENSE_CELLS);
+    let mut universe = Universe::from_rle("x = 3, y = 3\nbo$2bo$3o!", side, side, Placement::At { row: 5, col: 7 }).unwrap();
+    universe.set_cell(side - 1, side - 2, true);
+
+    let text = universe_to_rle(&universe);
+    assert_eq!(text, format!("x = 1048568, y = 1048571, rule = B3/S23:T{0},{0}\nbo$2bo$3o1048568$1048567bo!\n", side));
+    let pattern = parse_rle(&text).unwrap();
+    assert_eq!(pattern.live_cells(), vec![(0, 1), (1, 2), (2, 0), (2, 1), (2, 2), (1048570, 1048567)]);
+
+    let text = encode_universe(&universe, PatternFormat::Life106);
+    assert_eq!(text, format!("#Life 1.06\n8 5\n9 6\n7 7\n8 7\n9 7\n{} {}\n", side - 2, side - 1));
+
+    universe.set_cell(side - 1, side - 2, false);
+    assert_eq!(encode_universe(&universe, PatternFormat::Plaintext), ".O\n..O\nOOO\n");
+}
+
+#[test]
+fn encoded_rle_lines_wrap_at_70_columns() {
+    // Alternating cells make one token per cell
+    let cells: Vec<u8> = (0..3 * 101).map(|idx| ((idx % 101) % 2 == 0) as u8).collect();
+    let text = encode_rle(&cells, 101, 3, &Rule::conway(), Topology::Plane);
+    let body: Vec<&str> = text.lines().skip(1).collect();
+    assert!(body.len() > 4, "{}", text);
+    assert!(body.iter().all(|line| line.len() <= 70), "{}", text);
+    assert!(body[..body.len() - 1].iter().all(|line| line.len() >= 69), "{}", text);
+    // Counts are never split across lines
+    assert!(body.iter().all(|line| !line.ends_with(|c: char| c.is_ascii_digit())), "{}", text);
+
+    let pattern = parse_rle(&text).unwrap();
+    assert_eq!((pattern.width, pattern.height), (101, 3));
+    assert_eq!(pattern.cells().unwrap(), cells);
+}
+
+#[test]
+fn encoded_rle_reads_back() {
+    let mut cells = vec![0; 40 * 30];
+    for idx in (0..cells.len()).filter(|idx| idx * 7919 % 13 < 4) {
+        cells[idx] = 1;
+    }
+    let text = encode_rle(&cells, 40, 30, &Rule::conway(), Topology::Torus);
+    let pattern = parse_rle(&text).unwrap();
+    assert_eq!(pattern.metadata.topology, Some(Topology::Torus));
+    assert_eq!(pattern.place((40, 30), Placement::At { row: 0, col: 0 }).unwrap(), cells);
+
+    let rule = Rule::parse("B2/S34/C5").unwrap();
+    let states: Vec<u8> = (0..12 * 8).map(|idx| (idx * 31 % 7 % 5) as u8).collect();
+    let text = encode_rle(&states, 12, 8, &rule, Topology::Torus);
+    let pattern = parse_rle(&text).unwrap();
+    assert_eq!(pattern.metadata.rule, rule);
+    assert_eq!(pattern.place((12, 8), Placement::At { row: 0, col: 0 }).unwrap(), states);
+}
+
+#[test]
+fn plaintext_patterns_are_read() {
+    let pattern = parse_plaintext("!Name: Glider\n!Author: Richard K. Guy\n!A comment\n.O\n..O\nOOO\n").unwrap();
+    assert_eq!(pattern.metadata.name.as_deref(), Some("Glider"));
+    assert_eq!(pattern.metadata.author.as_deref(), Some("Richard K. Guy"));
+    assert_eq!(pattern.metadata.comments, vec!["A comment"]);
+    assert_eq!((pattern.width, pattern.height), (3, 3));
+    assert_eq!(pattern.cells().unwrap(), vec![0, 1, 0, 0, 0, 1, 1, 1, 1]);
+
+    let error = parse_plaintext("!Name: Typo\n.O\n.o\n").unwrap_err();
+    assert!(matches!(error, RleError::UnexpectedCell { line: 3, column: 2, found: 'o' }), "{:?}", error);
+}
+
+#[test]
+fn life106_patterns_are_read() {
+    let pattern = parse_life106("#Life 1.06\n#N Glider\n0 -1\n1 0\n-1 1\n0 1\n1 1\n").unwrap();
+    assert_eq!(pattern.metadata.name.as_deref(), Some("Glider"));
+    assert_eq!(pattern.metadata.offset, Some((-1, -1)));
+    assert_eq!((pattern.width, pattern.height), (3, 3));
+    assert_eq!(pattern.cells().unwrap(), vec![0, 1, 0, 0, 0, 1, 1, 1, 1]);
+
+    let error = parse_life106("#Life 1.06\n0 0\n  1 2 3\n").unwrap_err();
+    assert!(matches!(error, RleError::InvalidCoordinates { line: 3, column: 3, .. }), "{:?}", error);
+}
+
+#[test]
+fn life105_blocks_are_read() {
+    let text = "#Life 1.05\n\
+                #D Two gliders\n\
+                #D far apart\n\
+                #R 23/36\n\
+                #P -1 -1\n\
+                .*\n\
+                ..*\n\
+                ***\n\
+                #P 10 5\n\
+                *\n";
+    let pattern = parse_life105(text).unwrap();
+    assert_eq!(pattern.metadata.comments, vec!["Two gliders", "far apart"]);
+    assert_eq!(pattern.metadata.rule, Rule::parse("B36/S23").unwrap());
+    assert_eq!(pattern.metadata.offset, Some((-1, -1)));
+    assert_eq!((pattern.width, pattern.height), (12, 7));
+    assert_eq!(pattern.live_cells(), vec![(0, 1), (1, 2), (2, 0), (2, 1), (2, 2), (6, 11)]);
+
+    // #N asks for Conway's rules rather than naming the pattern
+    let pattern = parse_life105("#Life 1.05\n#N\n#P 0 0\n**\n").unwrap();
+    assert_eq!(pattern.metadata.rule, Rule::conway());
+    assert_eq!(pattern.metadata.name, None);
+
+    let error = parse_life105("#Life 1.05\n#P 0 0\n.*O\n").unwrap_err();
+    assert!(matches!(error, RleError::UnexpectedCell { line: 3, column: 3, found: 'O' }), "{:?}", error);
+    let error = parse_life105("#Life 1.05\n#P 0\n*\n").unwrap_err();
+    assert!(matches!(error, RleError::InvalidMetadata { line: 2, .. }), "{:?}", error);
+    let error = parse_life105("#Life 1.05\n#P 9223372036854775807 0\n.*\n").unwrap_err();
+    assert!(matches!(error, RleError::InvalidCoordinates { line: 3, column: 2, .. }), "{:?}", error);
+}
+
+#[test]
+fn formats_are_told_apart_by_their_content() {
+    let sniffed = [
+        ("#Life 1.06\n0 0\n", Some(PatternFormat::Life106)),
+        ("#Life 1.05\n#P 0 0\n*\n", Some(PatternFormat::Life105)),
+        ("\n!Name: Blinker\nOOO\n", Some(PatternFormat::Plaintext)),
+        (".O.\n.O.\n", Some(PatternFormat::Plaintext)),
+        ("#N Blinker\n#C comment\nx = 3, y = 1\n3o!", Some(PatternFormat::Rle)),
+        ("x = 3, y = 1\n3o!", Some(PatternFormat::Rle)),
+        // Life 1.06 without its #Life line could be anything
+        ("#N Blinker\n0 0\n", None),
+        ("0 0\n1 0\n", None),
+        ("", None),
+    ];
+    for (text, format) in sniffed {
+        assert_eq!(PatternFormat::sniff(text), format, "{:?}", text);
+    }
+
+    assert_eq!(PatternFormat::from_extension("grids/blom.RLE"), Some(PatternFormat::Rle));
+    assert_eq!(PatternFormat::from_extension("glider.cells"), Some(PatternFormat::Plaintext));
+    assert_eq!(PatternFormat::from_extension("glider.lif"), Some(PatternFormat::Life106));
+    assert_eq!(PatternFormat::from_extension("glider.txt"), None);
+
+    // The content wins over the extension, which wins over the RLE default
+    assert_eq!(PatternFormat::detect("glider.lif", "#Life 1.05\n*\n"), PatternFormat::Life105);
+    assert_eq!(PatternFormat::detect("glider.rle", "!Name: Glider\n.O\n"), PatternFormat::Plaintext);
+    assert_eq!(PatternFormat::detect("glider.lif", "0 0\n"), PatternFormat::Life106);
+    assert_eq!(PatternFormat::detect("glider", "0 0\n"), PatternFormat::Rle);
+
+    let blinker = "#Life 1.05\n#P 0 0\n***\n";
+    let pattern = parse_pattern(blinker, PatternFormat::detect("blinker.lif", blinker)).unwrap();
+    assert_eq!(pattern.live_cells(), vec![(0, 0), (0, 1), (0, 2)]);
+}
+
+#[test]
+fn golly_grids_are_read() {
+    let grids = [
+        ("T100,80", Topology::Torus, Some((100, 80))),
+        ("P64,32", Topology::Plane, Some((64, 32))),
+        ("K100*,80", Topology::KleinBottle, Some((100, 80))),
+        ("C30,20", Topology::CrossSurface, Some((30, 20))),
+        ("S64", Topology::Sphere, Some((64, 64))),
+        // Golly's infinite strips have a zero side
+        ("T0,80", Topology::Torus, None),
+    ];
+    for (spec, topology, size) in grids {
+        assert_eq!(Topology::from_golly(spec), Ok(topology), "{}", spec);
+        assert_eq!(Topology::golly_grid_size(spec), size, "{}", spec);
+    }
+    for topology in [Topology::Torus, Topology::Plane, Topology::KleinBottle, Topology::CrossSurface] {
+        let spec = topology.to_golly(100, 80).unwrap();
+        assert_eq!(Topology::from_golly(&spec), Ok(topology), "{}", spec);
+        assert_eq!(Topology::golly_grid_size(&spec), Some((100, 80)), "{}", spec);
+    }
+}
+
+#[test]
+fn golly_grids_we_cannot_model_are_rejected() {
+    // Our Klein bottle twists the top and bottom edges, Golly's K100,80* the left and right
+    for (spec, reason) in [
+        ("K100,80*", "only Klein bottles twisted on the top and bottom edges (Kw*,h) are supported"),
+        ("K100,80", "a Klein bottle needs its twisted pair of edges marked with *"),
+        ("K100*,80*", "a Klein bottle has only one twisted pair of edges"),
+        ("T100*,80", "only a Klein bottle has a twisted pair of edges"),
+        ("T100+5,80", "shifted edges are not supported"),
+        ("T100,80-3", "shifted edges are not supported"),
+        ("K100*,80+2", "shifted edges are not supported"),
+    ] {
+        assert_eq!(Topology::from_golly(spec), Err(TopologyError::Unsupported(spec.to_string(), reason)), "{}", spec);
+    }
+    assert_eq!(Topology::golly_grid_size("T100+5,80"), None);
+    assert_eq!(Topology::from_golly("Q10,10"), Err(TopologyError::Unknown("Q10,10".to_string())));
+
+    let error = parse_rle("x = 2, y = 1, rule = B3/S23:K100,80*\n2o!").unwrap_err();
+    assert!(matches!(error, RleError::InvalidTopology { line: 1, column: 29, .. }), "{:?}", error);
+    assert_eq!(
+        error.to_string(),
+        "line 1, column 29: unsupported Golly grid 'K100,80*': \
+         only Klein bottles twisted on the top and bottom edges (Kw*,h) are supported"
+    );
+
+    let error = Universe::from_macrocell("[M2] (golly 4.2)\n#R B3/S23:T100+5,80\n*$\n").err();
+    assert!(matches!(error, Some(MacrocellError::InvalidTopology { line: 2, .. })), "{:?}", error);
+}
@@ -0,0 +1,198 @@
+//! Hashlife's big steps, memory management and macrocell files
+mod common;
+
+use common::live;
+use wasm_game_of_life::hashlife::{MacrocellError, Universe, MAX_STEP_LOG2};
+use wasm_game_of_life::rules::Rule;
+use wasm_game_of_life::topology::Topology;
+use wasm_game_of_life::traits::LifeUniverse;
+use wasm_game_of_life::utils::Placement;
+
+const GLIDER: &str = "x = 3, y = 3\nbo$2bo$3o!";
+
+fn unbounded_glider() -> Universe {
+    let mut universe = Universe::from_rle(GLIDER, 16, 16, Placement::At { row: 0, col: 0 }).unwrap();
+    universe.set_topology(Topology::Unbounded);
+    universe
+}
+
+#[test]
+fn the_largest_step_moves_a_glider_across_the_plane() {
+    let mut universe = unbounded_glider();
+    let before = universe.bounding_box().unwrap();
+    universe.step_pow2(MAX_STEP_LOG2);
+
+    // A glider moves one cell down and right every 4 generations
+    let distance = 1i64 << (MAX_STEP_LOG2 - 2);
+    assert_eq!(universe.generation(), 1 << MAX_STEP_LOG2);
+    assert_eq!(universe.total_population(), 5);
+    assert_eq!(universe.bounding_box(), Some((before.0 + distance, before.1 + distance, before.2 + distance, before.3 + distance)));
+}
+
+#[test]
+#[should_panic(expected = "Cannot step by 2^60 generations")]
+fn steps_beyond_the_largest_are_rejected() {
+    unbounded_glider().step_pow2(MAX_STEP_LOG2 + 1);
+}
+
+#[test]
+fn steps_beyond_the_largest_jump_are_split() {
+    let mut universe = unbounded_glider();
+    universe.step(3 << MAX_STEP_LOG2);
+    assert_eq!(universe.generation(), 3 << MAX_STEP_LOG2);
+    assert_eq!(universe.total_population(), 5);
+}
+
+#[test]
+fn larger_steps_reuse_the_results_of_smaller_ones() {
+    let mut universe = unbounded_glider();
+    universe.step_pow2(12);
+    let first = universe.stats();
+    universe.step_pow2(13);
+    let second = universe.stats();
+
+    // Below the root levels a step of 2^13 asks the same questions as one of 2^12
+    assert!(second.cache_misses - first.cache_misses < first.cache_misses / 4, "{:?} then {:?}", first, second);
+    assert_eq!(universe.generation(), (1 << 12) + (1 << 13));
+    assert_eq!(universe.total_population(), 5);
+}
+
+#[test]
+fn jumps_match_single_generations() {
+    let mut jumping = unbounded_glider();
+    let mut ticking = unbounded_glider();
+    jumping.step_pow2(5);
+    for _ in 0..32 {
+        ticking.tick();
+    }
+    assert_eq!(jumping.bounding_box(), ticking.bounding_box());
+    assert_eq!(jumping.live_cells().count(), 5);
+    assert_eq!(jumping.live_cells().collect::<Vec<_>>(), ticking.live_cells().collect::<Vec<_>>());
+}
+
+/// A glider straddling the centre of a level-4 root, as Golly saves it: leaves for the
+/// north-east, south-west and south-east quadrants, then the root
+const GOLLY_GLIDER: &str = "[M2] (golly 4.2)
+#R B3/S23
+#G 100
+#C a glider at the origin
+$$$$$$$*$
+$.......*$
+.*$**$
+4 0 1 2 3
+";
+
+#[test]
+fn macrocell_files_written_by_golly_are_read() {
+    let mut universe = Universe::from_macrocell(GOLLY_GLIDER).unwrap();
+    assert_eq!(*universe.rule(), Rule::conway());
+    assert_eq!(universe.topology(), Topology::Unbounded);
+    assert_eq!(universe.generation(), 100);
+    assert_eq!(universe.total_population(), 5);
+    let (top, left, bottom, right) = universe.bounding_box().unwrap();
+    assert_eq!((bottom - top, right - left), (2, 2));
+
+    // Rows and columns of the cells relative to the top-left corner of the glider
+    let shape = |universe: &Universe| -> Vec<(i64, i64)> {
+        let (top, left, _, _) = universe.bounding_box().unwrap();
+        live(universe).into_iter().map(|(row, col)| (row as i64 - top, col as i64 - left)).collect()
+    };
+    assert_eq!(shape(&universe), vec![(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+    for _ in 0..4 {
+        universe.tick();
+    }
+    assert_eq!(universe.generation(), 104);
+    assert_eq!(universe.bounding_box(), Some((top + 1, left + 1, bottom + 1, right + 1)));
+    assert_eq!(shape(&universe), vec![(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+
+    // A bounded grid follows the rule after a colon
+    let torus = GOLLY_GLIDER.replace("#R B3/S23", "#R B36/S23:T32,24");
+    let universe = Universe::from_macrocell(&torus).unwrap();
+    assert_eq!(*universe.rule(), Rule::parse("B36/S23").unwrap());
+    assert_eq!(universe.topology(), Topology::Torus);
+    assert_eq!((universe.width(), universe.height()), (32, 24));
+    assert_eq!(universe.live_cells().count(), 5);
+
+    assert!(matches!(Universe::from_macrocell("#R B3/S23\n"), Err(MacrocellError::MissingHeader)));
+    let error = Universe::from_macrocell(&GOLLY_GLIDER.replace("#G 100", "#G many")).err();
+    assert!(matches!(error, Some(MacrocellError::InvalidNode { line: 3, .. })), "{:?}", error);
+    let error = Universe::from_macrocell(&GOLLY_GLIDER.replace("4 0 1 2 3", "4 0 1 2 5")).err();
+    assert!(matches!(error, Some(MacrocellError::InvalidNode { line: 8, .. })), "{:?}", error);
+}
+
+#[test]
+fn macrocell_files_read_back() {
+    let rle = std::fs::read_to_string("grids/blom.rle").unwrap();
+    let mut universe = Universe::from_rle(&rle, 64, 64, Placement::Centered).unwrap();
+    universe.set_topology(Topology::Unbounded);
+    universe.step(1000);
+    let text = universe.to_macrocell();
+    assert!(text.starts_with("[M2]") && text.contains("\n#G 1000\n"), "{}", text);
+
+    let mut read = Universe::from_macrocell(&text).unwrap();
+    assert_eq!(read.generation(), 1000);
+    assert_eq!(read.topology(), Topology::Unbounded);
+    assert_eq!((read.width(), read.height()), (64, 64));
+    assert_eq!(read.total_population(), universe.total_population());
+    assert_eq!(read.bounding_box(), universe.bounding_box());
+    assert_eq!(live(&read), live(&universe));
+    assert_eq!(read.to_macrocell(), text);
+
+    // Both go on to evolve alike
+    universe.step(64);
+    read.step(64);
+    assert_eq!(read.total_population(), universe.total_population());
+    assert_eq!(read.bounding_box(), universe.bounding_box());
+
+    let mut torus = Universe::from_rle(GLIDER, 40, 30, Placement::Centered).unwrap();
+    torus.set_rule(Rule::parse("B36/S23").unwrap());
+    let text = torus.to_macrocell();
+    let read = Universe::from_macrocell(&text).unwrap();
+    assert_eq!((read.width(), read.height(), read.topology()), (40, 30, Topology::Torus));
+    assert_eq!(*read.rule(), Rule::parse("B36/S23").unwrap());
+    assert_eq!(live(&read), live(&torus));
+}
+
+/// Blom from the middle of a 64x64 viewport on the unbounded plane
+fn unbounded_blom() -> Universe {
+    let rle = std::fs::read_to_string("grids/blom.rle").unwrap();
+    let mut universe = Universe::from_rle(&rle, 64, 64, Placement::Centered).unwrap();
+    universe.set_topology(Topology::Unbounded);
+    universe
+}
+
+#[test]
+fn a_small_memory_limit_collects_garbage_without_changing_the_outcome() {
+    let mut universe = unbounded_blom();
+    universe.set_memory_limit(1 << 20);
+    assert_eq!(universe.memory_limit(), 1 << 20);
+    universe.step(23314);
+
+    let stats = universe.stats();
+    assert!(stats.collections > 0, "{:?}", stats);
+    assert!(stats.cache_hits > 0, "{:?}", stats);
+    assert_eq!(universe.total_population(), 2740);
+}
+
+#[test]
+fn collecting_garbage_keeps_the_pattern() {
+    let mut universe = unbounded_blom();
+    universe.step(1000);
+    let before = universe.stats();
+    let (population, bounds, cells) = (universe.total_population(), universe.bounding_box(), live(&universe));
+
+    universe.collect_garbage();
+    let after = universe.stats();
+    assert_eq!(after.collections, before.collections + 1);
+    assert!(after.nodes < before.nodes && after.bytes_used < before.bytes_used, "{:?} then {:?}", before, after);
+    assert!(after.cache_entries <= before.cache_entries, "{:?} then {:?}", before, after);
+    assert_eq!((universe.total_population(), universe.bounding_box(), live(&universe)), (population, bounds, cells));
+
+    // The tree left behind still evolves like one that was never collected
+    let mut untouched = unbounded_blom();
+    untouched.step(1000);
+    universe.step(500);
+    untouched.step(500);
+    assert_eq!(universe.total_population(), untouched.total_population());
+    assert_eq!(universe.bounding_box(), untouched.bounding_box());
+}
@@ -0,0 +1,49 @@
+//! Benchmark jobs run in child processes of the `performance_test` binary
+use std::process::Command;
+use wasm_game_of_life::utils::read_results;
+
+fn performance_test(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_performance_test")).args(args).output().unwrap()
+}
+
+#[test]
+fn a_job_reports_every_run_on_stdout() {
+    let output = performance_test(&["grids/blom.rle", "--job", "--sizes", "32", "--engines", "sparse", "-i", "4", "-w", "0", "-r", "2"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[..3], ["job-result 1", "engine\tSparse", "grid\t32\t32"]);
+    let runs: Vec<&str> = lines.iter().copied().filter(|line| line.starts_with("run\t")).collect();
+    assert_eq!(runs.len(), 2);
+    assert!(runs.iter().all(|run| run.split('\t').nth(1).unwrap().split(',').count() == 4));
+    assert_eq!(lines.last(), Some(&"end"));
+}
+
+#[test]
+fn a_job_the_engine_cannot_run_is_skipped() {
+    let output = performance_test(&["grids/blom.rle", "--job", "--sizes", "32", "--engines", "naive", "-t", "unbounded"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("skipped\tthe unbounded topology is not supported"), "{}", stdout);
+}
+
+#[test]
+fn isolated_runs_write_the_results_of_every_engine() {
+    let output_path = std::env::temp_dir().join(format!("isolated_{}.csv", std::process::id()));
+    let output = performance_test(&[
+        "grids/blom.rle", "--isolate", "--sizes", "32", "--engines", "sparse,hashlife", "-i", "5", "-r", "2",
+        "-o", output_path.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let results = read_results(output_path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&output_path).unwrap();
+    let engines: Vec<&str> = results.results.iter().map(|result| result.engine.as_str()).collect();
+    assert_eq!(engines, ["Sparse", "Hashlife"]);
+    assert!(results.results.iter().all(|result| result.generation_ns.len() == 2 && result.size() == "32"));
+}
+
+#[test]
+fn timeout_needs_isolation() {
+    let output = performance_test(&["grids/blom.rle", "--timeout", "5"]);
+    assert_eq!(output.status.code(), Some(2));
+}
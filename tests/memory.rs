@@ -0,0 +1,35 @@
+//! Memory use of the process, with the counting allocator installed for this test binary
+use wasm_game_of_life::memory::{heap_stats, process_memory, reset_heap_stats, CountingAllocator, MemoryUse};
+
+#[global_allocator]
+static ALLOC: CountingAllocator = CountingAllocator;
+
+#[test]
+fn resident_set_size_is_read_from_proc() {
+    if cfg!(target_os = "linux") {
+        let process = process_memory().unwrap();
+        assert!(process.rss_kb > 0);
+        assert!(process.peak_rss_kb >= process.rss_kb);
+    }
+}
+
+#[test]
+fn heap_use_is_counted() {
+    // Tests run in parallel and allocate too, so only lower bounds hold
+    reset_heap_stats();
+    let buffer = vec![1u8; 1 << 20];
+    let during = heap_stats().unwrap();
+    drop(buffer);
+    assert!(during.allocated_bytes >= 1 << 20);
+    assert!(during.peak_bytes >= 1 << 20);
+    assert!(during.allocations >= 1);
+}
+
+#[test]
+fn memory_use_combines_the_larger_figures() {
+    let small = MemoryUse { rss_kb: 10, peak_rss_kb: 30, heap: None };
+    let large = MemoryUse { rss_kb: 20, peak_rss_kb: 25, heap: heap_stats() };
+    let max = small.max(large);
+    assert_eq!((max.rss_kb, max.peak_rss_kb), (20, 30));
+    assert_eq!(max.heap, large.heap);
+}
@@ -0,0 +1,200 @@
+//! Patterns with well-known behaviour, run on every engine
+mod common;
+
+use common::{all_engines, grid_with, live};
+use std::collections::BTreeSet;
+use wasm_game_of_life::topology::Topology;
+use wasm_game_of_life::traits::LifeUniverse;
+use wasm_game_of_life::utils::{parse_rle, read_pattern, Placement};
+use wasm_game_of_life::{hashed_parallel, hashlife, sparse_matrix};
+
+const BLOCK: &str = "x = 2, y = 2\n2o$2o!";
+const BLINKER: &str = "x = 3, y = 1\n3o!";
+const GLIDER: &str = "x = 3, y = 3\nbo$2bo$3o!";
+const PULSAR: &str = "x = 13, y = 13\n2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$\
+o4bobo4bo$o4bobo4bo2$2b3o3b3o!";
+const GOSPER_GUN: &str = "x = 36, y = 9\n24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$\
+2o8bo3bob2o4bobo$10bo5bo7bo$11bo3bo$12b2o!";
+
+#[test]
+fn block_is_still() {
+    let grid = grid_with(BLOCK, 16, 16, 7, 7);
+    for (name, mut universe) in all_engines(16, 16, &grid) {
+        let start = live(universe.as_ref());
+        for generation in 1..=10 {
+            universe.tick();
+            assert_eq!(live(universe.as_ref()), start, "{} changed the block at generation {}", name, generation);
+        }
+    }
+}
+
+#[test]
+fn blinker_has_period_two() {
+    let grid = grid_with(BLINKER, 16, 16, 8, 7);
+    for (name, mut universe) in all_engines(16, 16, &grid) {
+        let start = live(universe.as_ref());
+        universe.tick();
+        let vertical = live(universe.as_ref());
+        assert_eq!(vertical, [(7, 8), (8, 8), (9, 8)].iter().copied().collect(), "{} did not turn the blinker", name);
+        universe.tick();
+        assert_eq!(live(universe.as_ref()), start, "{} did not turn the blinker back", name);
+    }
+}
+
+#[test]
+fn glider_moves_one_cell_diagonally_every_four_generations() {
+    let grid = grid_with(GLIDER, 20, 20, 2, 2);
+    for (name, mut universe) in all_engines(20, 20, &grid) {
+        let start = live(universe.as_ref());
+        for lap in 1..=3 {
+            universe.step(4);
+            let moved: BTreeSet<_> = start.iter().map(|&(row, col)| (row + lap, col + lap)).collect();
+            assert_eq!(live(universe.as_ref()), moved, "{} moved the glider wrongly after {} generations", name, 4 * lap);
+        }
+    }
+}
+
+#[test]
+fn pulsar_has_period_three() {
+    let grid = grid_with(PULSAR, 21, 21, 4, 4);
+    for (name, mut universe) in all_engines(21, 21, &grid) {
+        let start = live(universe.as_ref());
+        assert_eq!(start.len(), 48);
+        for generation in 1..=2 {
+            universe.tick();
+            assert_ne!(live(universe.as_ref()), start, "{} repeated the pulsar at generation {}", name, generation);
+        }
+        universe.tick();
+        assert_eq!(live(universe.as_ref()), start, "{} did not repeat the pulsar after 3 generations", name);
+    }
+}
+
+#[test]
+fn gosper_gun_adds_a_glider_every_thirty_generations() {
+    // Dead edges far enough away that the first gliders do not crash back into the gun
+    let grid = grid_with(GOSPER_GUN, 80, 80, 1, 1);
+    for (name, mut universe) in all_engines(80, 80, &grid) {
+        universe.set_topology(Topology::Plane);
+        assert_eq!(universe.population(), 36);
+        for glider in 1..=4 {
+            universe.step(30);
+            assert_eq!(universe.population(), 36 + 5 * glider, "{} after {} generations", name, 30 * glider);
+        }
+    }
+}
+
+/// Blom runs for 23314 generations before settling at 2740 cells. Its gliders travel thousands
+/// of cells, so only the engines with an unbounded plane can hold it; Hashlife is fast enough
+/// to check on every run.
+#[test]
+fn blom_settles_at_2740_cells_on_hashlife() {
+    let text = std::fs::read_to_string("grids/blom.rle").unwrap();
+    let mut universe = hashlife::Universe::from_rle(&text, 64, 64, Placement::Centered).unwrap();
+    universe.set_topology(Topology::Unbounded);
+    universe.step(23314);
+    assert_eq!(universe.total_population(), 2740);
+}
+
+/// By the end blom spans 11593x11432 cells, a grid no dense engine can step 23314 times in a
+/// test. Its first 300 generations stay within 128x128 though, so every engine runs those, on
+/// the unbounded plane where it can and else on a plane with dead edges, and must match
+/// Hashlife on the unbounded plane cell for cell.
+#[test]
+fn blom_starts_alike_on_every_engine() {
+    let pattern = read_pattern("grids/blom.rle").unwrap();
+    let cells = pattern.place((128, 128), Placement::Centered).unwrap();
+    let mut reference = hashlife::Universe::from_matrix(128, 128, cells.clone());
+    reference.set_topology(Topology::Unbounded);
+
+    let mut engines = all_engines(128, 128, &cells);
+    for (_, universe) in &mut engines {
+        if universe.supports_topology(Topology::Unbounded) {
+            universe.set_topology(Topology::Unbounded);
+        } else {
+            universe.set_topology(Topology::Plane);
+        }
+    }
+    let unbounded: Vec<&str> =
+        engines.iter().filter(|(_, universe)| universe.topology() == Topology::Unbounded).map(|&(name, _)| name).collect();
+    assert_eq!(unbounded, ["Sparse", "HashParallel", "Hashlife"]);
+
+    for (generation, population) in [(150, 70), (300, 80)] {
+        reference.step(150);
+        assert_eq!(reference.total_population(), population);
+        for (name, universe) in &mut engines {
+            universe.step(150);
+            assert_eq!(live(universe.as_ref()), live(&reference), "{} after {} generations", name, generation);
+        }
+    }
+}
+
+/// A glider flies off the 16x16 viewport of the hash-set engines' unbounded plane and on
+/// beyond it, checked cell for cell against Hashlife on the way
+#[test]
+fn gliders_leave_the_viewport_of_the_unbounded_hash_set_engines() {
+    let cells = parse_rle(GLIDER).unwrap().live_cells();
+    let mut reference = hashlife::Universe::from_live_cells(16, 16, cells.clone());
+    let mut sparse = sparse_matrix::Universe::from_live_cells(16, 16, cells.clone());
+    let mut hashed = hashed_parallel::Universe::from_live_cells(16, 16, cells);
+    reference.set_topology(Topology::Unbounded);
+    sparse.set_topology(Topology::Unbounded);
+    hashed.set_topology(Topology::Unbounded);
+    let start = reference.bounding_box().unwrap();
+
+    let mut generation = 0;
+    for (target, in_viewport) in [(4, 5), (52, 5), (56, 1), (200, 0)] {
+        reference.step((target - generation) as u64);
+        sparse.step(target - generation);
+        hashed.step(target - generation);
+        generation = target;
+
+        // A glider moves one cell down and right every 4 generations
+        let distance = (generation / 4) as i64;
+        let expected = Some((start.0 + distance, start.1 + distance, start.2 + distance, start.3 + distance));
+        assert_eq!(reference.bounding_box(), expected);
+        assert_eq!(live(&reference).len(), in_viewport);
+        for (name, population, bounds, cells) in [
+            ("Sparse", sparse.total_population(), sparse.bounding_box(), live(&sparse)),
+            ("HashParallel", hashed.total_population(), hashed.bounding_box(), live(&hashed)),
+        ] {
+            assert_eq!(population, reference.total_population(), "{} after {} generations", name, generation);
+            assert_eq!(bounds, expected, "{} after {} generations", name, generation);
+            assert_eq!(cells, live(&reference), "{} after {} generations", name, generation);
+        }
+    }
+
+    // Cells off the viewport can be read and written by their signed coordinates
+    let far: BTreeSet<(i64, i64)> = sparse.all_live_cells().collect();
+    assert_eq!(far, hashed.all_live_cells().collect());
+    assert!(far.iter().all(|&(row, col)| sparse.get_cell_at(row, col) && hashed.get_cell_at(row, col)));
+    let (top, left, bottom, right) = reference.bounding_box().unwrap();
+    assert!(!sparse.get_cell_at(top - 1, left) && !hashed.get_cell_at(top - 1, left));
+
+    sparse.set_cell_at(-1000, right, true);
+    hashed.set_cell_at(-1000, right, true);
+    assert_eq!((sparse.total_population(), hashed.total_population()), (6, 6));
+    assert_eq!(sparse.bounding_box(), Some((-1000, left, bottom, right)));
+    assert_eq!(hashed.bounding_box(), Some((-1000, left, bottom, right)));
+    sparse.set_cell_at(-1000, right, false);
+    hashed.set_cell_at(-1000, right, false);
+    assert_eq!(sparse.bounding_box(), Some((top, left, bottom, right)));
+    assert_eq!(hashed.bounding_box(), Some((top, left, bottom, right)));
+}
+
+/// The other unbounded engines take minutes even with optimizations, about one for Sparse and
+/// four for HashParallel on a single core: run with `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn blom_settles_at_2740_cells_on_hash_set_engines() {
+    let pattern = read_pattern("grids/blom.rle").unwrap();
+
+    let mut sparse = sparse_matrix::Universe::from_live_cells(64, 64, pattern.live_cells());
+    sparse.set_topology(Topology::Unbounded);
+    sparse.step(23314);
+    assert_eq!(sparse.total_population(), 2740);
+
+    let mut hashed = hashed_parallel::Universe::from_live_cells(64, 64, pattern.live_cells());
+    hashed.set_topology(Topology::Unbounded);
+    hashed.step(23314);
+    assert_eq!(hashed.total_population(), 2740);
+}
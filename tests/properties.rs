@@ -0,0 +1,109 @@
+//! Randomized checks: every engine must evolve random soups exactly like the reference engine
+mod common;
+
+use common::{all_engines, live};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use wasm_game_of_life::rules::Rule;
+use wasm_game_of_life::topology::{Topology, ALL_TOPOLOGIES};
+use wasm_game_of_life::traits::LifeUniverse;
+use wasm_game_of_life::verify::compare_engines;
+
+/// Soups per check. Each one picks its own size, density and topology.
+const CASES: u64 = 8;
+const GENERATIONS: usize = 40;
+
+/// A random soup with a random bounded topology
+fn random_soup(rng: &mut StdRng) -> (usize, usize, Vec<u8>, Topology) {
+    let topology = loop {
+        let topology = ALL_TOPOLOGIES[rng.gen_range(0..ALL_TOPOLOGIES.len())];
+        if topology.is_bounded() {
+            break topology;
+        }
+    };
+    let width = rng.gen_range(3..32);
+    let height = if topology == Topology::Sphere { width } else { rng.gen_range(3..32) };
+    let density = rng.gen_range(0.1..0.6);
+    let cells = (0..width * height).map(|_| rng.gen_bool(density) as u8).collect();
+    (width, height, cells, topology)
+}
+
+/// Engines that can evolve `rule` on `topology`, with the reference engine first:
+/// the naive one, or the optimized one for Generations rules
+fn engines_for(width: usize, height: usize, cells: &[u8], rule: Rule, topology: Topology) -> Vec<(&'static str, Box<dyn LifeUniverse>)> {
+    let mut engines: Vec<_> = all_engines(width, height, cells)
+        .into_iter()
+        .filter(|(_, universe)| universe.supports_rule(&rule) && universe.supports_topology(topology))
+        .collect();
+    if let Some(reference) = engines.iter().position(|(name, _)| *name == "Naive" || *name == "Optimized") {
+        engines[..=reference].rotate_right(1);
+    }
+    for (_, universe) in engines.iter_mut() {
+        universe.set_rule(rule);
+        universe.set_topology(topology);
+    }
+    engines
+}
+
+/// Runs random soups under `rule` on every engine that supports it, failing at the first divergence
+fn check_rule(rule: &str, seed: u64) {
+    let rule = Rule::parse(rule).unwrap();
+    for case in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed + case);
+        let (width, height, cells, topology) = random_soup(&mut rng);
+        let mut engines = engines_for(width, height, &cells, rule, topology);
+        assert!(engines.len() > 1, "no engine to compare with the reference for {}", rule);
+        if let Err(divergence) = compare_engines(&mut engines, GENERATIONS, 1) {
+            panic!("{} on a {}x{} {} (seed {}): {}", rule, width, height, topology, seed + case, divergence);
+        }
+    }
+}
+
+#[test]
+fn engines_agree_on_conway() {
+    check_rule("B3/S23", 1000);
+}
+
+#[test]
+fn engines_agree_on_highlife() {
+    check_rule("B36/S23", 2000);
+}
+
+#[test]
+fn engines_agree_on_seeds() {
+    check_rule("B2/S", 3000);
+}
+
+#[test]
+fn engines_agree_on_a_non_totalistic_rule() {
+    check_rule("B3-q4z/S23k", 4000);
+}
+
+#[test]
+fn engines_agree_on_generations_rules() {
+    check_rule("B2/S/C3", 5000);
+    check_rule("B345/S2/C5", 6000);
+}
+
+#[test]
+fn step_matches_repeated_ticks() {
+    let mut rng = StdRng::seed_from_u64(7000);
+    for _ in 0..CASES {
+        let (width, height, cells, topology) = random_soup(&mut rng);
+        let generations = rng.gen_range(1..60);
+        let stepped = engines_for(width, height, &cells, Rule::conway(), topology);
+        let ticked = engines_for(width, height, &cells, Rule::conway(), topology);
+        for ((name, mut stepped), (_, mut ticked)) in stepped.into_iter().zip(ticked) {
+            stepped.step(generations);
+            for _ in 0..generations {
+                ticked.tick();
+            }
+            assert_eq!(
+                live(stepped.as_ref()),
+                live(ticked.as_ref()),
+                "{} on a {}x{} {}: step({}) differs from {} ticks",
+                name, width, height, topology, generations, generations
+            );
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Charts drawn by `performance_test report`
+use std::process::Command;
+
+#[test]
+fn report_draws_every_chart_of_a_results_file() {
+    let dir = std::env::temp_dir().join(format!("report_{}", std::process::id()));
+    let results = dir.join("blom.csv");
+    let program = env!("CARGO_BIN_EXE_performance_test");
+
+    let run = Command::new(program)
+        .args(["grids/blom.rle", "--sizes", "16,32", "--engines", "naive,sparse", "-i", "20", "-w", "0", "-r", "2", "-o"])
+        .arg(&results)
+        .output()
+        .unwrap();
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+
+    for format in ["png", "svg"] {
+        let report = Command::new(program).arg("report").arg(&results).arg("-o").arg(&dir).args(["-f", format]).output().unwrap();
+        assert!(report.status.success(), "{}", String::from_utf8_lossy(&report.stderr));
+        for chart in ["time_vs_size", "16_per_10_generations", "32_per_10_generations", "memory", "speedup"] {
+            let path = dir.join(format!("blom_{}.{}", chart, format));
+            assert!(std::fs::metadata(&path).is_ok_and(|file| file.len() > 0), "{} was not drawn", path.display());
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn report_needs_results_files() {
+    let output = Command::new(env!("CARGO_BIN_EXE_performance_test")).arg("report").output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,214 @@
+//! Rule strings, and the Hensel letters of non-totalistic rules checked against their
+//! neighbourhoods as drawn on LifeWiki and in Golly
+mod common;
+
+use common::{all_engines, grid_with, live};
+use std::collections::BTreeSet;
+use wasm_game_of_life::rules::{Rule, RuleError};
+
+#[test]
+fn birth_survival_forms() {
+    for rule in ["B3/S23", "b3/s23", " B3 / S23 ", "S23/B3", "23/3"] {
+        assert_eq!(Rule::parse(rule), Ok(Rule::conway()), "{}", rule);
+    }
+    let highlife = Rule::parse("B36/S23").unwrap();
+    assert!(highlife.is_totalistic() && highlife.is_birth(6) && !highlife.is_birth(2) && highlife.is_survival(2));
+    assert_eq!(Rule::parse("B36/S23"), Ok(Rule::new(&[3, 6], &[2, 3])));
+    let seeds: Rule = "B2/S".parse().unwrap();
+    assert_eq!(seeds, Rule::new(&[2], &[]));
+    assert!(!Rule::parse("B2-a/S12").unwrap().is_totalistic());
+}
+
+#[test]
+#[should_panic(expected = "B0 rules are not supported")]
+fn rules_built_from_counts_reject_birth_on_zero() {
+    Rule::new(&[0, 3], &[2, 3]);
+}
+
+#[test]
+#[should_panic(expected = "Neighbour counts must be 0 to 8, got B[3]/S[2, 9]")]
+fn rules_built_from_counts_reject_counts_above_eight() {
+    Rule::new(&[3], &[2, 9]);
+}
+
+#[test]
+fn generations_forms() {
+    let brians_brain = Rule::parse("B2/S/C3").unwrap();
+    assert_eq!(brians_brain.states(), 3);
+    assert!(brians_brain.is_generations());
+    for rule in ["B2/S/3", "b2/s/c3", "/2/3", "B2/S/G3"] {
+        assert_eq!(Rule::parse(rule), Ok(brians_brain), "{}", rule);
+    }
+    assert_eq!(Rule::parse("B3/S23/C2"), Ok(Rule::conway()));
+    assert_eq!(Rule::parse("B3/S23/C256").unwrap().states(), 256);
+}
+
+#[test]
+fn malformed_rules_are_rejected() {
+    let malformed = |rule: &str| RuleError::Malformed(rule.to_string());
+    for (rule, error) in [
+        ("", malformed("")),
+        ("B3", malformed("B3")),
+        ("B3/S23/C3/4", malformed("B3/S23/C3/4")),
+        ("B3/B36", malformed("B3/B36")),
+        ("B2-/S", malformed("B2-/S")),
+        ("B9/S23", RuleError::InvalidDigit('9')),
+        ("B3/Sx", RuleError::InvalidDigit('x')),
+        ("B2x/S", RuleError::InvalidLetter { count: 2, letter: 'x' }),
+        ("B1a/S", RuleError::InvalidLetter { count: 1, letter: 'a' }),
+        ("B3/S23/C1", RuleError::InvalidStates("C1".to_string())),
+        ("B3/S23/C257", RuleError::InvalidStates("C257".to_string())),
+        ("B3/S23/Cx", RuleError::InvalidStates("Cx".to_string())),
+        ("B03/S23", RuleError::BirthOnZero),
+    ] {
+        assert_eq!(Rule::parse(rule), Err(error), "{}", rule);
+    }
+}
+
+#[test]
+fn rules_display_in_canonical_form_and_read_back() {
+    for (rule, canonical) in [
+        ("B3/S23", "B3/S23"),
+        ("S23/B3", "B3/S23"),
+        ("23/36", "B36/S23"),
+        ("B2/S", "B2/S"),
+        ("B/S012345678", "B/S012345678"),
+        ("/2/3", "B2/S/C3"),
+        ("S345/B2/C4", "B2/S345/C4"),
+        ("B2-a/S12", "B2-a/S12"),
+        ("B2nc/S", "B2cn/S"),
+        // The shorter of the included and excluded letters is written
+        ("B2ceikn/S", "B2-a/S"),
+        ("B4ceaikn/S", "B4ceaikn/S"),
+        ("B2aceikn/S", "B2/S"),
+        ("B3-jknr4-ijq/S2-i34iz/C5", "B3-knjr4-ijq/S2-i34iz/C5"),
+    ] {
+        let parsed = Rule::parse(rule).unwrap();
+        assert_eq!(parsed.to_string(), canonical, "{}", rule);
+        assert_eq!(Rule::parse(canonical), Ok(parsed), "{}", canonical);
+    }
+}
+
+/// Neighbourhood index of a 3x3 picture such as "O.O/.../...", `O` marking live cells;
+/// bit `3 * row + col`, as `Rule::next_state_neighbourhood` takes it
+fn neighbourhood(picture: &str) -> usize {
+    picture
+        .split('/')
+        .flat_map(str::chars)
+        .enumerate()
+        .filter(|&(_, c)| c == 'O')
+        .fold(0, |index, (bit, _)| index | 1 << bit)
+}
+
+/// The eight rotations and reflections of a neighbourhood
+fn images(index: usize) -> BTreeSet<usize> {
+    let map = |index: usize, f: &dyn Fn(usize, usize) -> (usize, usize)| {
+        (0..9).filter(|bit| index & (1 << bit) != 0).map(|bit| f(bit / 3, bit % 3)).fold(0, |out, (row, col)| out | 1 << (3 * row + col))
+    };
+    let mut images = BTreeSet::new();
+    let mut image = index;
+    for _ in 0..4 {
+        image = map(image, &|row, col| (col, 2 - row));
+        images.insert(image);
+        images.insert(map(image, &|row, col| (row, 2 - col)));
+    }
+    images
+}
+
+/// Checks that `B<count><letter>/S` gives birth on exactly the images of `picture`
+fn check_letter(count: usize, letter: char, picture: &str) {
+    let shape = neighbourhood(picture);
+    assert_eq!(shape.count_ones() as usize, count, "{}{} is drawn with the wrong count", count, letter);
+    let rule = Rule::parse(&format!("B{}{}/S", count, letter)).unwrap();
+    let expected = images(shape);
+    for index in (0..512).filter(|index| index & 1 << 4 == 0) {
+        assert_eq!(rule.next_state_neighbourhood(index), expected.contains(&index), "{}{} on neighbourhood {:09b}", count, letter, index);
+    }
+}
+
+#[test]
+fn one_neighbour_letters() {
+    check_letter(1, 'c', "O../.../...");
+    check_letter(1, 'e', ".O./.../...");
+}
+
+#[test]
+fn two_neighbour_letters() {
+    check_letter(2, 'c', "O.O/.../...");
+    check_letter(2, 'e', ".O./O../...");
+    check_letter(2, 'k', "O../..O/...");
+    check_letter(2, 'a', "OO./.../...");
+    check_letter(2, 'i', ".../O.O/...");
+    check_letter(2, 'n', "..O/.../O..");
+}
+
+#[test]
+fn three_neighbour_letters() {
+    check_letter(3, 'c', "O.O/.../O..");
+    check_letter(3, 'e', ".O./O.O/...");
+    check_letter(3, 'k', ".O./..O/O..");
+    check_letter(3, 'a', "OO./O../...");
+    check_letter(3, 'i', "OOO/.../...");
+    check_letter(3, 'n', "O.O/O../...");
+    check_letter(3, 'y', "O../..O/O..");
+    check_letter(3, 'q', ".OO/.../O..");
+    check_letter(3, 'j', ".OO/O../...");
+    check_letter(3, 'r', "O../O.O/...");
+}
+
+#[test]
+fn four_neighbour_letters() {
+    check_letter(4, 'c', "O.O/.../O.O");
+    check_letter(4, 'e', ".O./O.O/.O.");
+    check_letter(4, 'k', "OO./..O/O..");
+    check_letter(4, 'a', "OOO/O../...");
+    check_letter(4, 'i', "O.O/O.O/...");
+    check_letter(4, 'n', "OOO/.../O..");
+    check_letter(4, 'y', "O.O/..O/O..");
+    check_letter(4, 'q', ".OO/..O/O..");
+    check_letter(4, 'j', ".O./O.O/O..");
+    check_letter(4, 'r', "OO./O.O/...");
+    check_letter(4, 't', "O../O.O/O..");
+    check_letter(4, 'w', ".OO/O../O..");
+    check_letter(4, 'z', "..O/O.O/O..");
+}
+
+#[test]
+fn higher_counts_are_the_complements_of_lower_ones() {
+    // 5c has the three dead neighbours of 3c's live ones the other way round
+    let three = Rule::parse("B3c/S").unwrap();
+    let five = Rule::parse("B5c/S").unwrap();
+    for index in (0..512).filter(|index| index & 1 << 4 == 0) {
+        assert_eq!(three.next_state_neighbourhood(index), five.next_state_neighbourhood(0b1_1110_1111 ^ index));
+    }
+    check_letter(7, 'e', "OOO/O.O/O.O");
+    check_letter(6, 'i', "OOO/.../OOO");
+}
+
+/// Two cells with a gap: the cell between them sees 2i, the cells above and below it 2c.
+/// Births on 2c alone turn the pair into a vertical one and back, a period 2 oscillator;
+/// with births on 2i too it becomes a vertical line instead, which dies out because its
+/// neighbours see 2a, 3 or a single cell.
+#[test]
+fn letters_decide_the_fate_of_a_pattern_on_every_engine() {
+    let pair = "x = 3, y = 1\nobo!";
+    let expected = |cells: &[(usize, usize)]| cells.iter().copied().collect::<BTreeSet<_>>();
+    for (rule, after_one, after_two) in [
+        ("B2i/S", expected(&[(4, 5)]), expected(&[])),
+        ("B2c/S", expected(&[(3, 5), (5, 5)]), expected(&[(4, 4), (4, 6)])),
+        ("B2ci/S", expected(&[(3, 5), (4, 5), (5, 5)]), expected(&[])),
+        ("B2-ci/S", expected(&[]), expected(&[])),
+    ] {
+        let rule = Rule::parse(rule).unwrap();
+        for (name, mut universe) in all_engines(10, 10, &grid_with(pair, 10, 10, 4, 4)) {
+            if !universe.supports_rule(&rule) {
+                continue;
+            }
+            universe.set_rule(rule);
+            universe.tick();
+            assert_eq!(live(universe.as_ref()), after_one, "{} under {}", name, rule);
+            universe.tick();
+            assert_eq!(live(universe.as_ref()), after_two, "{} under {}", name, rule);
+        }
+    }
+}